@@ -0,0 +1,423 @@
+//! Actions of the exec clause
+//!
+//! By default a command is not run by a shell. Its template is split into words
+//! like a shell would do it, before any attribute is substituted, and every word is
+//! rendered into exactly one argument. Names containing spaces, quotes or `$(...)`
+//! are therefore passed on unchanged. Only `exec sh '...'` runs a script with `sh -c`.
+//!
+//! Scripts, whether given with `exec sh '...'` or as the argument of `-c` of a shell,
+//! are parsed by a shell after the attributes are substituted. There the value of
+//! every `{{ ... }}` is shell-quoted, unless it already uses `shell_quote`.
+//!
+//! Common file operations like `delete` or `move to '...'` are built in and run
+//! without a process, see the `builtin` module.
+//!
+//! All templates are parsed when the query is built, so a broken template is
+//! rejected before the first file is visited.
+
+use liquid;
+use liquid::{Context, Renderable, Template};
+use regex::{Captures, Regex};
+use std::ffi::OsString;
+use std::mem;
+use std::path::Path;
+use std::process::Command;
+use template_filters;
+use builtin::Builtin;
+
+/// Programs whose `-c` argument is a script.
+const SHELLS: [&str; 6] = ["sh", "bash", "dash", "zsh", "ksh", "mksh"];
+
+/// A word of a command, which is rendered into arguments.
+pub enum Word {
+    /// A word consisting only of `{{names}}`, which is replaced by one argument per name of a batch.
+    Names,
+    /// A template for exactly one argument.
+    Template(Template),
+}
+
+/// The command line of an action.
+pub enum CommandLine {
+    /// Words of the command.
+    Words(Vec<Word>),
+    /// Template for a script, which is run with `sh -c`.
+    Shell(Template),
+}
+
+/// A parsed command together with the variables its templates reference.
+pub struct CommandTemplate {
+    pub line: CommandLine,
+    variables: Vec<String>,
+    length: usize,
+    embeds_names: bool,
+}
+
+impl CommandTemplate {
+    /// Parses a command, which is split into words and run without a shell.
+    pub fn words(command: &str) -> Result<CommandTemplate, String> {
+        let mut words = Vec::new();
+        let mut length = 0;
+        let mut embeds_names = false;
+        let split = split_words(command)?;
+        let script = script_index(&split);
+        for (i, word) in split.iter().enumerate() {
+            length += word.len() + 1 + mem::size_of::<usize>();
+            if Some(i) == script {
+                embeds_names |= referenced_variables(word).iter().any(|v| v == "names");
+                words.push(Word::Template(parse_template(&quote_outputs(word))?));
+            } else if is_names_word(word) {
+                words.push(Word::Names);
+            } else {
+                embeds_names |= referenced_variables(word).iter().any(|v| v == "names");
+                words.push(Word::Template(parse_template(&join_names(word))?));
+            }
+        }
+        Ok(CommandTemplate{line: CommandLine::Words(words), variables: referenced_variables(command), length, embeds_names})
+    }
+
+    /// Parses a script for `sh -c`.
+    pub fn shell(script: &str) -> Result<CommandTemplate, String> {
+        let variables = referenced_variables(script);
+        let embeds_names = variables.iter().any(|v| v == "names");
+        Ok(CommandTemplate{line: CommandLine::Shell(parse_template(&quote_outputs(script))?), variables, length: script.len(), embeds_names})
+    }
+
+    /// Number of bytes the command line needs in the argument list, without substituted attributes.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_shell(&self) -> bool {
+        match self.line {
+            CommandLine::Shell(_) => true,
+            CommandLine::Words(_) => false,
+        }
+    }
+
+    /// The names of all variables which may be referenced by the templates.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// True if `names` is rendered as text somewhere, rather than only being a word
+    /// of its own, which passes the names on as they are.
+    pub fn embeds_names(&self) -> bool {
+        self.embeds_names
+    }
+
+    /// Renders the command into a process and a description of it for messages.
+    /// A word `{{names}}` is replaced by the given names.
+    pub fn build(&self, context: &mut Context, names: &[OsString]) -> Result<(Command, String), String> {
+        match self.line {
+            CommandLine::Shell(ref script) => {
+                let rendered = render(script, context)?;
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(&rendered);
+                Ok((command, rendered))
+            },
+            CommandLine::Words(ref words) => {
+                let mut argv: Vec<OsString> = Vec::new();
+                for word in words {
+                    match *word {
+                        Word::Names            => argv.extend(names.iter().cloned()),
+                        Word::Template(ref t)  => argv.push(OsString::from(render(t, context)?)),
+                    }
+                }
+                if argv.is_empty() {
+                    return Err(String::from("The rendered command is empty."));
+                }
+                let mut command = Command::new(&argv[0]);
+                command.args(&argv[1..]);
+                let description = argv.iter().map(|a| template_filters::quote_if_needed(&a.to_string_lossy())).collect::<Vec<String>>().join(" ");
+                Ok((command, description))
+            },
+        }
+    }
+}
+
+/// A template for a single path, e.g. the destination of `move to '...'`.
+pub struct PathTemplate {
+    pub template: Template,
+    variables: Vec<String>,
+}
+
+impl PathTemplate {
+    pub fn new(path: &str) -> Result<PathTemplate, String> {
+        Ok(PathTemplate{template: parse_template(path)?, variables: referenced_variables(path)})
+    }
+
+    /// The names of all variables which may be referenced by the template.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+/// The action which is performed for the entries passing the filter expression.
+pub enum Action {
+    /// Runs the command once per entry.
+    Exec(CommandTemplate),
+    /// Runs the command once per batch of entries, which are available as the `names` array.
+    Batch(CommandTemplate),
+    /// Asks on the terminal before the command is run for an entry.
+    Confirm(CommandTemplate),
+    /// Runs the command once per entry inside the entry's parent directory,
+    /// where `name` is `./` followed by the basename.
+    ExecDir(CommandTemplate),
+    /// Performs a built-in file operation without starting a process.
+    Builtin(Builtin),
+}
+
+/// Renders a template.
+pub fn render(template: &Template, context: &mut Context) -> Result<String, String> {
+    template.render(context)
+            .map(|r| r.unwrap_or_default())
+            .map_err(|e| format!("Command template error: {}", e))
+}
+
+pub fn parse_template(template: &str) -> Result<Template, String> {
+    check_filters(template)?;
+    liquid::parse(template, Default::default()).map_err(|e| format!("Command template error in {}: {}", template, e))
+}
+
+/// Liquid only notices unknown filters while rendering, so they are checked up front.
+fn check_filters(template: &str) -> Result<(), String> {
+    let tag = Regex::new(r"\{\{.*?\}\}").unwrap();
+    let filter = Regex::new(r"\|\s*([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    for t in tag.find_iter(template) {
+        for f in filter.captures_iter(&strip_literals(t.as_str())) {
+            let name = f.get(1).map(|m| m.as_str()).unwrap_or("");
+            if !template_filters::is_known(name) {
+                return Err(format!("Command template error in {}: unknown filter {}", template, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_names_word(word: &str) -> bool {
+    let word = word.trim();
+    word.starts_with("{{") && word.ends_with("}}") && word[2..word.len() - 2].trim() == "names"
+}
+
+/// Returns the index of the script in a command like `sh -c '...'` or `bash -ec '...'`.
+fn script_index(words: &[String]) -> Option<usize> {
+    let program = words.first().and_then(|w| Path::new(w).file_name()).and_then(|n| n.to_str());
+    if !program.is_some_and(|p| SHELLS.contains(&p)) {
+        return None;
+    }
+    words.iter()
+         .skip(1)
+         .position(|w| w.starts_with('-') && !w.starts_with("--") && w.contains('c'))
+         .map(|i| i + 2)
+         .filter(|&i| i < words.len())
+}
+
+/// Shell-quotes the value of every `{{ ... }}` of a script which does not use
+/// `shell_quote` itself, so that no attribute can inject commands.
+fn quote_outputs(script: &str) -> String {
+    let tag = Regex::new(r"\{\{(.*?)\}\}").unwrap();
+    let quoted = Regex::new(r"\|\s*shell_quote\b").unwrap();
+    tag.replace_all(script, |c: &Captures| {
+        if quoted.is_match(&strip_literals(&c[1])) {
+            c[0].to_string()
+        } else {
+            format!("{{{{{} | shell_quote}}}}", &c[1])
+        }
+    }).into_owned()
+}
+
+/// Joins every plain `{{names}}` which is not a word of its own with spaces, e.g. in
+/// `--files={{names}}`. No shell parses these, so the names are not quoted.
+fn join_names(template: &str) -> String {
+    let names = Regex::new(r"\{\{\s*names\s*\}\}").unwrap();
+    names.replace_all(template, "{{names | join: ' '}}").into_owned()
+}
+
+/// Removes the string literals from a liquid tag.
+fn strip_literals(tag: &str) -> String {
+    let literal = Regex::new(r#"'[^']*'|"[^"]*""#).unwrap();
+    literal.replace_all(tag, "").into_owned()
+}
+
+/// Collects all identifiers inside of liquid tags. Besides the variables this also
+/// contains filter names and keywords, which never match an attribute.
+pub fn referenced_variables(template: &str) -> Vec<String> {
+    let tag = Regex::new(r"\{\{.*?\}\}|\{%.*?%\}").unwrap();
+    let identifier = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+    let mut variables: Vec<String> = Vec::new();
+    for t in tag.find_iter(template) {
+        for i in identifier.find_iter(&strip_literals(t.as_str())) {
+            if !variables.iter().any(|v| v == i.as_str()) {
+                variables.push(String::from(i.as_str()));
+            }
+        }
+    }
+    variables
+}
+
+/// Returns the length of the liquid tag at the start of `s`, if there is one.
+fn liquid_tag_length(s: &str, command: &str) -> Result<Option<usize>, String> {
+    let end_tag = if s.starts_with("{{") {
+        "}}"
+    } else if s.starts_with("{%") {
+        "%}"
+    } else {
+        return Ok(None);
+    };
+    match s.find(end_tag) {
+        Some(e) => Ok(Some(e + end_tag.len())),
+        None    => Err(format!("'{}' is not closed in command {}", &s[..2], command)),
+    }
+}
+
+/// Splits a command template into words.
+/// Words are separated by whitespace, single and double quotes group words and a
+/// backslash escapes the next character. Liquid tags (`{{ ... }}` and `{% ... %}`)
+/// are copied verbatim, so their filters may contain spaces and quotes.
+pub fn split_words(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut rest = command;
+
+    while let Some(c) = rest.chars().next() {
+        if quote != Some('\'') {
+            if let Some(length) = liquid_tag_length(rest, command)? {
+                word.push_str(&rest[..length]);
+                in_word = true;
+                rest = &rest[length..];
+                continue;
+            }
+        }
+        rest = &rest[c.len_utf8()..];
+        match (quote, c) {
+            (Some(q), c) if q == c => quote = None,
+            (Some('"'), '\\') => {
+                match rest.chars().next() {
+                    Some(e) if e == '"' || e == '\\' => {
+                        word.push(e);
+                        rest = &rest[1..];
+                    },
+                    _ => word.push('\\'),
+                }
+            },
+            (Some(_), c) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, '\\') => {
+                if let Some(e) = rest.chars().next() {
+                    word.push(e);
+                    rest = &rest[e.len_utf8()..];
+                }
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(word);
+                    word = String::new();
+                    in_word = false;
+                }
+            },
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("Missing closing {} in command {}", q, command));
+    }
+    if in_word {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err(String::from("The exec clause does not contain a command."));
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, split_words, CommandTemplate, CommandLine, Word};
+    use liquid::{Context, Value};
+    use template_filters;
+
+    fn words(command: &str) -> Vec<String> {
+        split_words(command).unwrap()
+    }
+
+    #[test]
+    fn splits_at_whitespace() {
+        assert_eq!(words("  rm -f\t{{name}} "), vec!["rm", "-f", "{{name}}"]);
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(words("echo 'a b' \"c d\" e' 'f"), vec!["echo", "a b", "c d", "e f"]);
+        assert_eq!(words("echo '' \"\""), vec!["echo", "", ""]);
+    }
+
+    #[test]
+    fn backslashes_escape() {
+        assert_eq!(words(r"echo a\ b \' \\"), vec!["echo", "a b", "'", "\\"]);
+        assert_eq!(words(r#"echo "a\"b" "c\d" 'e\f'"#), vec!["echo", "a\"b", "c\\d", "e\\f"]);
+    }
+
+    #[test]
+    fn liquid_tags_stay_whole() {
+        assert_eq!(words("cp {{name}} {{ name | append: '.bak' }}"), vec!["cp", "{{name}}", "{{ name | append: '.bak' }}"]);
+        assert_eq!(words("echo {% if size > 0 %}x{% endif %}"), vec!["echo", "{% if size > 0 %}x{% endif %}"]);
+        assert_eq!(words("echo '{{name}}'"), vec!["echo", "{{name}}"]);
+    }
+
+    #[test]
+    fn rejects_broken_commands() {
+        assert!(split_words("echo 'a").is_err());
+        assert!(split_words("echo \"a").is_err());
+        assert!(split_words("echo {{name").is_err());
+        assert!(split_words("   ").is_err());
+    }
+
+    fn context() -> Context {
+        let mut context = Context::new();
+        template_filters::register(&mut context);
+        context.set_val("name", Value::Str(String::from("$(touch PWNED).txt")));
+        context.set_val("names", Value::Array(vec![Value::Str(String::from("a b")), Value::Str(String::from("$(id)"))]));
+        context
+    }
+
+    /// Renders the word of a command at the given index.
+    fn rendered_word(template: &CommandTemplate, index: usize) -> String {
+        match template.line {
+            CommandLine::Words(ref words) => match words[index] {
+                Word::Template(ref t) => render(t, &mut context()).unwrap(),
+                Word::Names           => panic!("not a template"),
+            },
+            CommandLine::Shell(ref script) => render(script, &mut context()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn quotes_values_in_scripts() {
+        let template = CommandTemplate::shell("rm {{names}} {{ names }}; echo {{name}} {{name | shell_quote}}.gz {{ name | upcase }}").unwrap();
+        assert_eq!(rendered_word(&template, 0), "rm 'a b' '$(id)' 'a b' '$(id)'; echo '$(touch PWNED).txt' '$(touch PWNED).txt'.gz '$(TOUCH PWNED).TXT'");
+        assert!(template.embeds_names());
+        let template = CommandTemplate::words("bash -ec 'rm {{names}}' {{name}}").unwrap();
+        assert_eq!(rendered_word(&template, 2), "rm 'a b' '$(id)'");
+        assert_eq!(rendered_word(&template, 3), "$(touch PWNED).txt");
+        assert!(template.embeds_names());
+    }
+
+    #[test]
+    fn joins_embedded_names_outside_of_scripts() {
+        let template = CommandTemplate::words("echo --files={{names}} {{name}}").unwrap();
+        assert_eq!(rendered_word(&template, 1), "--files=a b $(id)");
+        assert_eq!(rendered_word(&template, 2), "$(touch PWNED).txt");
+        assert!(template.embeds_names());
+        assert!(!CommandTemplate::words("rm {{names}}").unwrap().embeds_names());
+        assert!(!CommandTemplate::words("echo -c {{names}}").unwrap().embeds_names());
+    }
+}
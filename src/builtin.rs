@@ -0,0 +1,207 @@
+//! Built-in file operations
+//!
+//! These actions take the place of the exec clause and work without spawning a
+//! process, so they neither depend on coreutils nor on the shell quoting names.
+//!
+//!   * `delete` removes files immediately and directories after the walk, deepest first,
+//!     a directory only if all of its entries were deleted as well
+//!   * `move to '<template>'` and `copy to '<template>'` create missing parent directories
+//!     and never overwrite an existing destination
+//!   * `chmod 644` sets the octal mode
+//!   * `chown user:group` accepts names or ids, either part may be omitted
+//!   * `touch` sets access and modification time to now
+//!
+//! Symbolic links are never followed: `chown` and `touch` change the link itself,
+//! `chmod` refuses them, as Linux has no mode for links.
+
+use action::PathTemplate;
+use libc;
+use std::ffi::CString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{PermissionsExt, symlink};
+use std::path::Path;
+use std::ptr;
+
+pub enum Builtin {
+    Delete,
+    Move(PathTemplate),
+    Copy(PathTemplate),
+    Chmod(u32),
+    /// New owner and group, `None` keeps the current one.
+    Chown(Option<u32>, Option<u32>),
+    Touch,
+}
+
+impl Builtin {
+    /// Parses the octal mode of `chmod`.
+    pub fn chmod(mode: &str) -> Result<Builtin, String> {
+        u32::from_str_radix(mode, 8)
+            .map(Builtin::Chmod)
+            .map_err(|_| format!("{} is not an octal file mode.", mode))
+    }
+
+    /// Parses the `user:group` of `chown`, users and groups are resolved right away.
+    pub fn chown(owner: &str) -> Result<Builtin, String> {
+        let mut parts = owner.splitn(2, ':');
+        let user = parts.next().unwrap_or("");
+        let group = parts.next().unwrap_or("");
+        let uid = if user.is_empty() { None } else { Some(lookup_user(user)?) };
+        let gid = if group.is_empty() { None } else { Some(lookup_group(group)?) };
+        if uid.is_none() && gid.is_none() {
+            return Err(format!("chown {} names neither a user nor a group.", owner));
+        }
+        Ok(Builtin::Chown(uid, gid))
+    }
+
+    /// The template of the destination path, if the operation has one.
+    pub fn destination(&self) -> Option<&PathTemplate> {
+        match *self {
+            Builtin::Move(ref d) | Builtin::Copy(ref d) => Some(d),
+            _                                            => None,
+        }
+    }
+
+    /// Describes the operation on `path` for messages and dry runs.
+    pub fn describe(&self, path: &Path, destination: Option<&Path>) -> String {
+        let target = destination.map(|d| d.display().to_string()).unwrap_or_default();
+        match *self {
+            Builtin::Delete        => format!("delete {}", path.display()),
+            Builtin::Move(_)       => format!("move {} to {}", path.display(), target),
+            Builtin::Copy(_)       => format!("copy {} to {}", path.display(), target),
+            Builtin::Chmod(mode)   => format!("chmod {:o} {}", mode, path.display()),
+            Builtin::Chown(u, g)   => format!("chown {}:{} {}",
+                                              u.map(|u| u.to_string()).unwrap_or_default(),
+                                              g.map(|g| g.to_string()).unwrap_or_default(),
+                                              path.display()),
+            Builtin::Touch         => format!("touch {}", path.display()),
+        }
+    }
+
+    /// Performs the operation. Directories are not deleted here, as their contents
+    /// are visited afterwards, see `delete_dir`.
+    pub fn run(&self, path: &Path, is_dir: bool, destination: Option<&Path>) -> io::Result<()> {
+        match *self {
+            Builtin::Delete      => if is_dir { Ok(()) } else { fs::remove_file(path) },
+            Builtin::Move(_)     => move_path(path, is_dir, destination.unwrap()),
+            Builtin::Copy(_)     => copy_path(path, is_dir, destination.unwrap()),
+            Builtin::Chmod(mode) => chmod(path, mode),
+            Builtin::Chown(u, g) => chown(path, u, g),
+            Builtin::Touch       => touch(path),
+        }
+    }
+}
+
+/// Removes a directory, which has to be empty by now.
+pub fn delete_dir(path: &Path) -> io::Result<()> {
+    match fs::remove_dir(path) {
+        Err(ref e) if e.raw_os_error() == Some(libc::ENOTEMPTY) => {
+            Err(io::Error::other("it still contains entries which did not pass the filter"))
+        },
+        result => result,
+    }
+}
+
+/// Fails if something exists at `destination`, even a broken symlink.
+fn check_free(destination: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(destination) {
+        Ok(_)                                           => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", destination.display()))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e)                                          => Err(e),
+    }
+}
+
+fn create_parent(destination: &Path) -> io::Result<()> {
+    match destination.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _                                             => Ok(()),
+    }
+}
+
+fn move_path(path: &Path, is_dir: bool, destination: &Path) -> io::Result<()> {
+    check_free(destination)?;
+    create_parent(destination)?;
+    match fs::rename(path, destination) {
+        Err(ref e) if e.raw_os_error() == Some(libc::EXDEV) && !is_dir => {
+            copy_path(path, is_dir, destination)?;
+            fs::remove_file(path)
+        },
+        result => result,
+    }
+}
+
+fn copy_path(path: &Path, is_dir: bool, destination: &Path) -> io::Result<()> {
+    create_parent(destination)?;
+    if is_dir {
+        return fs::create_dir(destination);
+    }
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return symlink(fs::read_link(path)?, destination);
+    }
+    let mut source = File::open(path)?;
+    // create_new fails if the destination exists, without a window to replace it
+    let mut target = OpenOptions::new().write(true).create_new(true).open(destination)?;
+    io::copy(&mut source, &mut target)?;
+    target.set_permissions(source.metadata()?.permissions())
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn chmod(path: &Path, mode: u32) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "it is a symbolic link, whose target is not changed"));
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+fn chown(path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    let c_path = c_path(path)?;
+    // -1 keeps the current owner or group
+    let uid = uid.unwrap_or(u32::MAX);
+    let gid = gid.unwrap_or(u32::MAX);
+    if unsafe { libc::lchown(c_path.as_ptr(), uid, gid) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn touch(path: &Path) -> io::Result<()> {
+    let c_path = c_path(path)?;
+    // without explicit times both are set to the current time
+    if unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), ptr::null(), libc::AT_SYMLINK_NOFOLLOW) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn lookup_user(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+    let name = CString::new(user).map_err(|_| format!("Invalid user name {}", user))?;
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        Err(format!("Unknown user {}", user))
+    } else {
+        Ok(unsafe { (*passwd).pw_uid })
+    }
+}
+
+fn lookup_group(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+    let name = CString::new(group).map_err(|_| format!("Invalid group name {}", group))?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        Err(format!("Unknown group {}", group))
+    } else {
+        Ok(unsafe { (*entry).gr_gid })
+    }
+}
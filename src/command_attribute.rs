@@ -0,0 +1,154 @@
+//! Virtual attributes from the output of commands
+//!
+//! `cmd('file -b')` runs a command for every entry and uses its stdout, without the
+//! trailing newline, as value. The command is a template like in the exec clause.
+//! If it does not reference any variable, the path of the entry is appended as last
+//! argument. Outputs are cached for the current entry, so that filtering and printing
+//! share one invocation.
+
+use action::CommandTemplate;
+use libc;
+use template_context;
+use walkdir::DirEntry;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Outputs of the current entry, keyed by command.
+struct Cache {
+    templates: HashMap<String, CommandTemplate>,
+    entry: PathBuf,
+    outputs: HashMap<String, Result<String, String>>,
+}
+
+/// Runs the commands of the `cmd(...)` attributes of a query.
+pub struct CommandAttributes {
+    machine_mode: bool,
+    timeout: Duration,
+    cache: RefCell<Cache>,
+}
+
+impl Default for CommandAttributes {
+    fn default() -> CommandAttributes {
+        CommandAttributes::new()
+    }
+}
+
+impl CommandAttributes {
+    pub fn new() -> CommandAttributes {
+        CommandAttributes{
+            machine_mode: false,
+            timeout: Duration::from_secs(10),
+            cache: RefCell::new(Cache{templates: HashMap::new(), entry: PathBuf::new(), outputs: HashMap::new()}),
+        }
+    }
+
+    /// Sets after how long a command is killed.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the output mode used for the variables of the templates.
+    pub fn set_machine_mode(&mut self, machine_mode: bool) {
+        self.machine_mode = machine_mode;
+    }
+
+    /// Returns the output of `command` for the entry found below `root`, running
+    /// it only once per entry.
+    pub fn output(&self, dir_entry: &DirEntry, root: &Path, command: &str) -> Result<String, String> {
+        let mut cache = self.cache.borrow_mut();
+        if cache.entry != dir_entry.path() {
+            cache.entry = dir_entry.path().to_path_buf();
+            cache.outputs.clear();
+        }
+        if let Some(output) = cache.outputs.get(command) {
+            return output.clone();
+        }
+        if !cache.templates.contains_key(command) {
+            let template = CommandTemplate::words(command)?;
+            cache.templates.insert(String::from(command), template);
+        }
+        let output = {
+            let template = &cache.templates[command];
+            template_context::setup_context(dir_entry, template.variables(), root, self.machine_mode)
+                .and_then(|mut context| template.build(&mut context, &[]))
+                .and_then(|(mut process, description)| {
+                    if template.variables().is_empty() {
+                        process.arg(dir_entry.path());
+                    }
+                    run(process, &description, self.timeout)
+                })
+        };
+        cache.outputs.insert(String::from(command), output.clone());
+        output
+    }
+}
+
+/// Checks the command template, so that a broken one is rejected before the search starts.
+pub fn check(command: &str) -> Result<(), String> {
+    CommandTemplate::words(command).map(|_| ())
+}
+
+fn run(mut command: Command, description: &str, timeout: Duration) -> Result<String, String> {
+    // a process group of its own, so that a timeout also kills whatever the command started
+    let mut child = command.stdin(Stdio::null())
+                           .stdout(Stdio::piped())
+                           .stderr(Stdio::piped())
+                           .process_group(0)
+                           .spawn()
+                           .map_err(|e| format!("Failed to start command {}: {}", description, e))?;
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    // read in threads, a full pipe would block the command forever
+    let (closed, pipes_closed) = mpsc::channel();
+    let stdout_closed = closed.clone();
+    let stdout_reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let result = stdout.read_to_end(&mut output).map(|_| output);
+        stdout_closed.send(()).ok();
+        result
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut output = String::new();
+        let result = stderr.read_to_string(&mut output).map(|_| output);
+        closed.send(()).ok();
+        result
+    });
+
+    // the pipes are closed when the command and everything it started are done
+    let deadline = Instant::now() + timeout;
+    let mut open_pipes = 2;
+    while open_pipes > 0 {
+        match pipes_closed.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(())                              => open_pipes -= 1,
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout)      => {
+                // the command is not waited for yet, so its pid still names its group
+                unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+                child.wait().ok();
+                stdout_reader.join().ok();
+                stderr_reader.join().ok();
+                return Err(format!("Command {} timed out after {}s", description, timeout.as_secs()));
+            },
+        }
+    }
+
+    let status = child.wait().map_err(|e| format!("Failed to wait for command {}: {}", description, e))?;
+    let output = stdout_reader.join().unwrap_or_else(|_| Ok(Vec::new())).unwrap_or_default();
+    let errors = stderr_reader.join().unwrap_or_else(|_| Ok(String::new())).unwrap_or_default();
+    if !status.success() {
+        return Err(format!("Command {} failed with {}: {}", description, status, errors.trim_end()));
+    }
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    while output.ends_with('\n') || output.ends_with('\r') {
+        output.pop();
+    }
+    Ok(output)
+}
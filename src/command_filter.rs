@@ -0,0 +1,64 @@
+use filter;
+use filter::Filter;
+use filter::Search;
+use walkdir::DirEntry;
+use regex::Regex;
+use std::process;
+use std::cmp::Ordering;
+
+/// Compares the output of a command. `~` matches a regular expression, the other
+/// operators compare numerically if both sides are numbers and as strings otherwise.
+pub struct CommandFilter {
+    command: String,
+    comp_op: filter::CompOp,
+    parameter: String,
+    regex: Option<Regex>,
+}
+
+impl CommandFilter {
+    pub fn new(command: &str, comp_op: filter::CompOp, parameter: &str) -> CommandFilter {
+        let regex = match comp_op {
+            filter::CompOp::Like | filter::CompOp::Unlike => match Regex::new(parameter) {
+                Ok(r)  => Some(r),
+                Err(e) => {
+                    eprintln!("Regex error {}", e);
+                    process::exit(1);
+                },
+            },
+            _ => None,
+        };
+        CommandFilter{command: String::from(command), comp_op, parameter: String::from(parameter), regex}
+    }
+}
+
+impl Filter for CommandFilter {
+    fn test(&self, dir_entry: &DirEntry, search: &Search) -> bool {
+        let output = match search.commands.output(dir_entry, search.root, &self.command) {
+            Ok(o)  => o,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            },
+        };
+        if let Some(ref regex) = self.regex {
+            return regex.is_match(&output) != (self.comp_op == filter::CompOp::Unlike);
+        }
+        let ordering = match (output.trim().parse::<f64>(), self.parameter.parse::<f64>()) {
+            (Ok(o), Ok(p)) => o.partial_cmp(&p),
+            _              => Some(output.as_str().cmp(self.parameter.as_str())),
+        };
+        let ordering = match ordering {
+            Some(o) => o,
+            None    => return false,
+        };
+        match self.comp_op {
+            filter::CompOp::Lower        => ordering == Ordering::Less,
+            filter::CompOp::LowerEqual   => ordering != Ordering::Greater,
+            filter::CompOp::Equal        => ordering == Ordering::Equal,
+            filter::CompOp::Unequal      => ordering != Ordering::Equal,
+            filter::CompOp::GreaterEqual => ordering != Ordering::Less,
+            filter::CompOp::Greater      => ordering == Ordering::Greater,
+            _                            => false,
+        }
+    }
+}
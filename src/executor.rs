@@ -0,0 +1,168 @@
+//! Executor for the commands of the exec clause
+//!
+//! Commands are started in the order of the matching entries and at most `jobs`
+//! of them run at the same time. Every child is waited for and a non-zero exit
+//! status is reported on stderr. In dry-run mode the commands are only printed.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::io;
+use std::mem;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus};
+use libc;
+
+/// Returns the maximum length of a command line for a batch, which is the system's `ARG_MAX`
+/// minus the size of the environment and some headroom. If the names end up in a
+/// `single_argument`, like in a script for `sh -c`, Linux additionally limits it to 32 pages.
+pub fn max_command_length(single_argument: bool) -> usize {
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    let arg_max = if arg_max > 0 { arg_max as usize } else { 32 * 4096 };
+    let env_size: usize = env::vars_os().map(|(k, v)| k.len() + v.len() + 2 + mem::size_of::<usize>()).sum();
+    let available = arg_max.saturating_sub(env_size).saturating_sub(2048);
+    if single_argument {
+        cmp::min(available, 32 * 4096)
+    } else {
+        available
+    }
+}
+
+/// Answer to the question whether a command should be run.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+/// Asks on the terminal whether `description` should be run. The question is
+/// written to stderr and the answer read from the TTY, as stdin may be in use.
+pub fn ask(description: &str) -> Result<Answer, String> {
+    let tty = File::open("/dev/tty").map_err(|e| format!("Cannot ask for confirmation without a terminal: {}", e))?;
+    let mut reader = BufReader::new(tty);
+    loop {
+        eprint!("{}? [y]es, [n]o, [a]ll, [q]uit: ", description);
+        io::stderr().flush().ok();
+        let mut answer = String::new();
+        match reader.read_line(&mut answer) {
+            Ok(0)  => return Ok(Answer::Quit),
+            Ok(_)  => {},
+            Err(e) => {
+                eprintln!("Could not read answer: {}", e);
+                return Ok(Answer::Quit);
+            },
+        }
+        match answer.trim() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "n" | "no"  => return Ok(Answer::No),
+            "a" | "all" => return Ok(Answer::All),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _           => {},
+        }
+    }
+}
+
+pub struct Executor {
+    jobs: usize,
+    dry_run: bool,
+    running: VecDeque<(String, Child)>,
+    failed: usize,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Executor{jobs: 1, dry_run: false, running: VecDeque::new(), failed: 0}
+    }
+
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = if jobs == 0 { 1 } else { jobs };
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Starts `command` and, if `jobs` commands are running now, waits until one of them finished.
+    /// `description` is used for error messages.
+    pub fn spawn(&mut self, mut command: Command, description: String) {
+        if self.dry_run {
+            println!("{}", description);
+            return;
+        }
+        match command.spawn() {
+            Ok(child) => self.running.push_back((description, child)),
+            Err(e)    => {
+                eprintln!("Failed to start command {}: {}", description, e);
+                self.failed += 1;
+            },
+        }
+        while self.running.len() >= self.jobs {
+            self.wait_any();
+        }
+    }
+
+    /// Reports a failed built-in operation, which counts like a failed command.
+    pub fn report_failure(&mut self, message: &str) {
+        eprintln!("{}", message);
+        self.failed += 1;
+    }
+
+    /// Waits for all running commands and returns the number of commands which failed.
+    pub fn finish(&mut self) -> usize {
+        while !self.running.is_empty() {
+            self.wait_any();
+        }
+        self.failed
+    }
+
+    /// Waits until any of the running commands finished, not necessarily the oldest one.
+    /// The commands of the pool are the only children which are not waited for
+    /// elsewhere, so whichever child `waitpid` returns belongs to the pool.
+    fn wait_any(&mut self) {
+        let mut status = 0;
+        let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+        if pid < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                return;
+            }
+            // no child left to wait for, so none of the pool is running anymore
+            for (description, _) in self.running.drain(..) {
+                eprintln!("Failed to wait for command {}: {}", description, error);
+                self.failed += 1;
+            }
+            return;
+        }
+        if let Some(i) = self.running.iter().position(|(_, child)| child.id() as libc::pid_t == pid) {
+            let (description, _) = self.running.remove(i).unwrap();
+            let status = ExitStatus::from_raw(status);
+            self.check_status(&description, status.success(), status.code());
+        }
+    }
+
+    fn check_status(&mut self, description: &str, success: bool, code: Option<i32>) {
+        if success {
+            return;
+        }
+        self.failed += 1;
+        match code {
+            Some(c) => eprintln!("Command {} exited with status {}", description, c),
+            None    => eprintln!("Command {} was terminated by a signal", description),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use filter::Filter;
+use filter::Search;
 use walkdir::DirEntry;
 use std::process;
 
@@ -34,7 +35,7 @@ impl FiletypeFilter {
 }
 
 impl Filter for FiletypeFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         match self.filetype {
             Filetype::Dir => dir_entry.metadata().unwrap().file_type().is_dir(),
             Filetype::File => dir_entry.metadata().unwrap().file_type().is_file(),
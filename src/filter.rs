@@ -1,4 +1,7 @@
 use walkdir::DirEntry;
+use command_attribute::CommandAttributes;
+use paths;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 #[derive(Debug)]
@@ -21,16 +24,89 @@ pub enum Attribute {
     Name,
     Size,
     Mtime,
+    /// Status change time of the inode.
     Ctime,
     Atime,
+    /// Birth time, if the filesystem records it.
+    Btime,
     Filetype,
     Mimetype,
     Inode,
     Basename,
     Uid,
     Gid,
+    /// Absolute path without resolving symlinks.
+    Abspath,
+    /// Path relative to the search root.
+    Relpath,
+    /// Absolute path with all symlinks resolved.
+    Realpath,
+    /// Output of a command template, see `command_attribute`.
+    Command(String),
+}
+
+/// An attribute of the select part, which may be renamed with `as`.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Column {
+    pub attribute: Attribute,
+    pub alias: Option<String>,
+}
+
+impl Attribute {
+    /// Computes the attributes which are paths derived from the path of an entry
+    /// found below `root`.
+    pub fn derived_path(&self, dir_entry: &DirEntry, root: &Path) -> Option<PathBuf> {
+        match *self {
+            Attribute::Abspath  => Some(paths::absolute(dir_entry.path())),
+            Attribute::Relpath  => Some(paths::root_relative(dir_entry.path(), root)),
+            Attribute::Realpath => Some(paths::canonical(dir_entry.path())),
+            _                   => None,
+        }
+    }
+}
+
+impl Column {
+    pub fn new(attribute: Attribute, alias: Option<String>) -> Column {
+        Column{attribute, alias}
+    }
+
+    /// The alias of the column or else the name of its attribute as written in a query.
+    pub fn name(&self) -> String {
+        if let Some(ref alias) = self.alias {
+            return alias.clone();
+        }
+        let name = match self.attribute {
+            Attribute::Name         => "name",
+            Attribute::Size         => "size",
+            Attribute::Mtime        => "mtime",
+            Attribute::Ctime        => "ctime",
+            Attribute::Atime        => "atime",
+            Attribute::Btime        => "btime",
+            Attribute::Filetype     => "type",
+            Attribute::Mimetype     => "mimetype",
+            Attribute::Inode        => "inode",
+            Attribute::Basename     => "basename",
+            Attribute::Uid          => "uid",
+            Attribute::Gid          => "gid",
+            Attribute::Abspath      => "abspath",
+            Attribute::Relpath      => "relpath",
+            Attribute::Realpath     => "realpath",
+            Attribute::Command(ref command) => return format!("cmd('{}')", command),
+        };
+        String::from(name)
+    }
+}
+
+/// What a filter may need to know about the search besides the entry itself.
+pub struct Search<'a> {
+    /// The directory of the from clause the entry was found in.
+    pub root: &'a Path,
+    /// Runs the commands of `cmd(...)` attributes.
+    pub commands: &'a CommandAttributes,
 }
 
 pub trait Filter{
-    fn test(&self, dir_entry: &DirEntry) -> bool;
+    fn test(&self, dir_entry: &DirEntry, search: &Search) -> bool;
 }
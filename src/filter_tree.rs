@@ -1,14 +1,17 @@
 use filter::Filter;
+use filter::Search;
 use filter::CompOp;
 use filter::Attribute;
 use regex_filter::RegexFilter;
 use name_filter::EqualNameFilter;
 use name_filter::EqualBasenameFilter;
+use name_filter::EqualPathFilter;
 use size_filter::SizeFilter;
 use time_filter::TimeFilter;
 use filetype_filter::FiletypeFilter;
 use uid_filter::UidFilter;
 use gid_filter::GidFilter;
+use command_filter::CommandFilter;
 use walkdir::DirEntry;
 use std::process;
 
@@ -27,11 +30,11 @@ pub struct FilterTuple {
 
 impl FilterTuple {
     pub fn new(attribute: Attribute, operator: CompOp, parameter: String) -> FilterTuple {
-        FilterTuple{attribute: attribute, operator: operator, parameter: parameter}
+        FilterTuple{attribute, operator, parameter}
     }
 }
 
-pub fn create_filter(inp: FilterTuple) -> Box<Filter> {
+pub fn create_filter(inp: FilterTuple) -> Box<dyn Filter> {
     match inp.attribute {
         Attribute::Name => {
             match inp.operator {
@@ -44,6 +47,17 @@ pub fn create_filter(inp: FilterTuple) -> Box<Filter> {
                 },
             }
         },
+        Attribute::Abspath | Attribute::Relpath | Attribute::Realpath => {
+            match inp.operator {
+                CompOp::Equal => Box::new(EqualPathFilter::new(inp.attribute.clone(), inp.parameter.as_str())),
+                CompOp::Like => Box::new(RegexFilter::new(&inp)),
+                CompOp::Unlike => Box::new(RegexFilter::new(&inp)),
+                _            => {
+                    eprintln!("Operator is not implemented for attribute.");
+                    process::exit(1);
+                }
+            }
+        },
         Attribute::Basename => {
             match inp.operator {
                 CompOp::Equal => Box::new(EqualBasenameFilter::new(inp.parameter.as_str())),
@@ -67,6 +81,9 @@ pub fn create_filter(inp: FilterTuple) -> Box<Filter> {
         Attribute::Ctime => {
             Box::new(TimeFilter::new(inp.attribute, inp.operator, inp.parameter.as_str()))
         },
+        Attribute::Btime => {
+            Box::new(TimeFilter::new(inp.attribute, inp.operator, inp.parameter.as_str()))
+        },
         Attribute::Filetype => {
             Box::new(FiletypeFilter::new(inp.parameter.as_str()))
         },
@@ -76,6 +93,9 @@ pub fn create_filter(inp: FilterTuple) -> Box<Filter> {
         Attribute::Gid => {
             Box::new(GidFilter::new(inp.operator, inp.parameter.parse::<u32>().unwrap()))
         },
+        Attribute::Command(ref command) => {
+            Box::new(CommandFilter::new(command, inp.operator.clone(), inp.parameter.as_str()))
+        },
         _               => {
             eprintln!("Not yet implemented!");
             process::exit(1);
@@ -84,7 +104,7 @@ pub fn create_filter(inp: FilterTuple) -> Box<Filter> {
 }
 
 pub struct FilterTree {
-    lhs: Option<Box<Filter>>,
+    lhs: Option<Box<dyn Filter>>,
     lop: Option<LogicOp>,
     rhs: Option<Box<FilterTree>>,
 }
@@ -106,10 +126,10 @@ impl FilterTree {
             eprintln!("Right hand side is missing!");
             process::exit(1);
         }
-        FilterTree{lhs: Some(create_filter(lhs.unwrap())), lop: op, rhs: rhs}
+        FilterTree{lhs: Some(create_filter(lhs.unwrap())), lop: op, rhs}
     }
 
-    pub fn test(&self, dir_entry: &DirEntry) -> bool {
+    pub fn test(&self, dir_entry: &DirEntry, search: &Search) -> bool {
         if self.lhs.as_ref().is_none() {
             return true
         }
@@ -118,11 +138,11 @@ impl FilterTree {
                 let rhs = self.rhs.as_ref().unwrap();
                 let lhs = self.lhs.as_ref().unwrap();
                 match *op {
-                    LogicOp::And => lhs.test(dir_entry) && rhs.test(dir_entry),
-                    LogicOp::Or  => lhs.test(dir_entry) || rhs.test(dir_entry),
+                    LogicOp::And => lhs.test(dir_entry, search) && rhs.test(dir_entry, search),
+                    LogicOp::Or  => lhs.test(dir_entry, search) || rhs.test(dir_entry, search),
                 }
             },
-            None => self.lhs.as_ref().unwrap().test(dir_entry),
+            None => self.lhs.as_ref().unwrap().test(dir_entry, search),
         }
     }
 }
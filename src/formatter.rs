@@ -1,7 +1,5 @@
 use walkdir::DirEntry;
-use termion::color;
 use std::path::Path;
-use std::fmt;
 use mime_guess;
 use std::fs;
 use pretty_bytes::converter::convert as pretty_bytes_convert;
@@ -10,47 +8,30 @@ use chrono::{Local, TimeZone};
 use ColorConfig;
 use std::os::unix::fs::FileTypeExt;
 use std::process;
-
-struct RgbColor {
-    red: u8,
-    green: u8,
-    blue: u8,
-}
-
-impl RgbColor {
-    fn from_str(color_string: &str) -> RgbColor {
-        let red = u8::from_str_radix(&color_string[0..2], 16).expect(&format!("Could not parse red value in color {}", color_string));
-        let green = u8::from_str_radix(&color_string[2..4], 16).expect(&format!("Could not parse green value in color {}", color_string));
-        let blue = u8::from_str_radix(&color_string[4..6], 16).expect(&format!("Could not parse blue value in color {}", color_string));
-        RgbColor{red: red, green: green, blue: blue}
-    }
-
-    fn as_color(&self) -> color::Rgb {
-        color::Rgb(self.red, self.green, self.blue)
-    }
-}
-
-impl fmt::Display for RgbColor {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "rgb({}, {}, {})", self.red, self.green, self.blue)
-    }
-}
+use std::ffi::CStr;
+use libc;
+use ls_colors;
+use hyperlink;
+use palette;
+use palette::Color;
+use output;
+use timestamps;
 
 fn get_path_name(p: &Path) -> String {
     match p.to_str() {
-        Some(s) => format!("{}", s),
+        Some(s) => s.to_string(),
         None    => {
             eprintln!("Error: UTF-8 Error");
-            format!("")
+            String::new()
         },
     }
 }
 
-pub fn format_mimetype(t: mime_guess::Mime, machine_readable: bool) -> String {
+pub fn format_mimetype(t: mime_guess::Mime, _machine_readable: bool) -> String {
     format!("{}", t)
 }
 
-pub fn format_filetype(ft: fs::FileType, machine_readable: bool) -> String {
+pub fn format_filetype(ft: fs::FileType, _machine_readable: bool) -> String {
     if ft.is_file() {
         String::from("file")
     } else if ft.is_dir() {
@@ -70,26 +51,47 @@ pub fn format_filetype(ft: fs::FileType, machine_readable: bool) -> String {
 
 pub fn format_filesize(size: u64, machine_readable: bool) -> String {
     if machine_readable {
-        return format!("{}", size)
+        format!("{}", size)
     } else {
         pretty_bytes_convert(size as f64)
     }
 }
 
+/// Returns the name of the user with the given id, or the id if there is no such user.
+pub fn format_user(uid: u32) -> String {
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return format!("{}", uid);
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }.to_string_lossy().into_owned()
+}
+
+/// Returns the name of the group with the given id, or the id if there is no such group.
+pub fn format_group(gid: u32) -> String {
+    let group = unsafe { libc::getgrgid(gid) };
+    if group.is_null() {
+        return format!("{}", gid);
+    }
+    unsafe { CStr::from_ptr((*group).gr_name) }.to_string_lossy().into_owned()
+}
+
 pub fn format_systime(t: time::SystemTime, machine_readable: bool) -> String {
-    let duration = t.duration_since(time::UNIX_EPOCH).unwrap();
     if machine_readable {
-        format!("{}", duration.as_secs())
+        timestamps::epoch_string(t)
     } else {
-       format!("{}", Local.timestamp(duration.as_secs() as i64, 0).format("%F %T"))
+        format!("{}", Local.timestamp(output::epoch_seconds(t), 0).format("%F %T"))
     }
 }
 
-pub fn format_path<P: color::Color, C: color::Color>(path: &Path, parent_color: color::Fg<P>, filename_color: color::Fg<C>) -> String {
+pub fn format_path(path: &Path, parent_color: Color, filename_color: Color) -> String {
     match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => format!("{reset}{filename_color}{filename}{reset}",
+                                                                 reset=Color::Reset,
+                                                                 filename_color=filename_color,
+                                                                 filename=get_path_name(path)),
         Some(parent) => match path.file_name() {
             Some(filename) => format!("{reset}{path_color}{path}/{filename_color}{filename}{reset}", 
-                                          reset=color::Fg(color::Reset),
+                                          reset=Color::Reset,
                                           path_color=parent_color,
                                           path=get_path_name(parent), 
                                           filename_color=filename_color,
@@ -100,7 +102,7 @@ pub fn format_path<P: color::Color, C: color::Color>(path: &Path, parent_color:
                                                     ""
                                                 },
                                           }),
-            None => format!("{}", get_path_name(parent)),
+            None => get_path_name(parent).to_string(),
         },
         None => {
             eprintln!("This should not happen!");
@@ -109,120 +111,76 @@ pub fn format_path<P: color::Color, C: color::Color>(path: &Path, parent_color:
     }
 }
 
-pub fn format_dir<P: color::Color>(path: &Path, dir_color: color::Fg<P>) -> String {
+pub fn format_dir(path: &Path, dir_color: Color) -> String {
     format!("{reset}{dircolor}{dirname}{reset}", 
-                                reset=color::Fg(color::Reset),
+                                reset=Color::Reset,
                                 dircolor=dir_color,
                                 dirname=get_path_name(path))
 }
 
+/// Returns true if the config file sets any color, which then takes precedence over `LS_COLORS`.
+fn has_configured_colors(color_config: &Option<ColorConfig>) -> bool {
+    color_config.as_ref().map(palette::has_colors).unwrap_or(false)
+}
+
+/// Colors a path with the colors of `LS_COLORS`, if it is set.
+/// `display` is the text which is printed for `path`.
+fn format_ls_colors(display: &Path, path: &Path, filetype: fs::FileType) -> Option<String> {
+    if filetype.is_dir() {
+        return ls_colors::paint(&get_path_name(display), path, filetype);
+    }
+    match (display.parent(), display.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            let prefix = ls_colors::paint_directory(&format!("{}/", get_path_name(parent)))?;
+            Some(format!("{}{}", prefix, ls_colors::paint(&name.to_string_lossy(), path, filetype)?))
+        },
+        _ => ls_colors::paint(&get_path_name(display), path, filetype),
+    }
+}
 
+/// Formats a name in the color of the given file type, e.g. the basenames of the tree view.
+pub fn format_typed_name(name: &str, path: &Path, filetype: fs::FileType, color_config: &Option<ColorConfig>, color_mode: bool, hyperlinks: bool) -> String {
+    hyperlink::wrap(path, color_typed_name(name, path, filetype, color_config, color_mode), hyperlinks)
+}
 
-pub fn format_name(dir_entry: &DirEntry, color_config: &Option<ColorConfig>, color_mode: bool) -> String {
-    let path = dir_entry.path();
-    let default_format = get_path_name(path);
+fn color_typed_name(name: &str, path: &Path, filetype: fs::FileType, color_config: &Option<ColorConfig>, color_mode: bool) -> String {
     if !color_mode {
-        return default_format;
+        return String::from(name);
+    }
+    if !has_configured_colors(color_config) {
+        if let Some(painted) = ls_colors::paint(name, path, filetype) {
+            return painted;
+        }
+    }
+    match palette::of(color_config).color_for(path, filetype) {
+        Some(color) => format!("{}{}{}", color, name, Color::Reset),
+        None        => String::from(name),
     }
+}
 
-    // Default configuration taken from LS_COLORS aka the output of /usr/bin/dircolors
-    let file_color = color::Fg(color::Reset);
-    let dir_color = color::Fg(color::Blue);
-    let symlink_color = color::Fg(color::Cyan);
-    let block_device_color = color::Fg(color::LightRed);
-    let fifo_color = color::Fg(color::LightRed);
-    let socket_color = color::Fg(color::Magenta);
+/// Formats the path of an entry as `display`, which may be another form of it.
+/// It is colored if `color_mode` is on and a link if `hyperlinks` is.
+pub fn format_name(dir_entry: &DirEntry, display: &Path, color_config: &Option<ColorConfig>, color_mode: bool, hyperlinks: bool) -> String {
+    hyperlink::wrap(dir_entry.path(), color_name(dir_entry, display, color_config, color_mode), hyperlinks)
+}
 
+fn color_name(dir_entry: &DirEntry, display: &Path, color_config: &Option<ColorConfig>, color_mode: bool) -> String {
+    let path = dir_entry.path();
+    let default_format = get_path_name(display);
+    if !color_mode {
+        return default_format;
+    }
     let filetype = dir_entry.file_type();
-    match *color_config {
-        Some(ref config) => match config.prefix {
-            Some(ref pre) => { 
-                if filetype.is_file(){
-                    match config.file {
-                        Some(ref fc) => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), color::Fg(RgbColor::from_str(fc).as_color())),
-                        None         => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), file_color),
-                    }
-                } else if filetype.is_symlink() {
-                    match config.symlink {
-                        Some(ref sc) => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), color::Fg(RgbColor::from_str(sc).as_color())),
-                        None         => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), symlink_color),
-                    }
-                } else if filetype.is_block_device() {
-                    match config.device {
-                        Some(ref bc) => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), color::Fg(RgbColor::from_str(bc).as_color())),
-                        None         => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), block_device_color),
-                    }
-                } else if filetype.is_fifo() {
-                    match config.fifo {
-                            Some(ref fc) => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), color::Fg(RgbColor::from_str(fc).as_color())),
-                            None         => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), fifo_color),
-                    }
-                } else if filetype.is_socket() {
-                    match config.socket {
-                        Some(ref sc) => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), color::Fg(RgbColor::from_str(sc).as_color())),
-                        None         => format_path(path, color::Fg(RgbColor::from_str(pre).as_color()), socket_color),
-                    }    
-                } else if filetype.is_dir() {
-                    match config.dir {
-                        Some(ref dc) => format_dir(path, color::Fg(RgbColor::from_str(dc).as_color())),
-                        None => format_dir(path, dir_color),
-                    }
-                } else {
-                    format!("{}", get_path_name(path))
-                }
-            },
-            None         => { 
-                if filetype.is_file(){
-                    match config.file {
-                        Some(ref fc) => format_path(path, dir_color, color::Fg(RgbColor::from_str(fc).as_color())),
-                        None         => format_path(path, dir_color, file_color),
-                    }
-                } else if filetype.is_symlink() {
-                    match config.symlink {
-                        Some(ref sc) => format_path(path, dir_color, color::Fg(RgbColor::from_str(sc).as_color())),
-                        None         => format_path(path, dir_color, symlink_color),
-                    }
-                } else if filetype.is_block_device() {
-                    match config.device {
-                        Some(ref bc) => format_path(path, dir_color, color::Fg(RgbColor::from_str(bc).as_color())),
-                        None         => format_path(path, dir_color, block_device_color),
-                    }
-                } else if filetype.is_fifo() {
-                    match config.fifo {
-                            Some(ref fc) => format_path(path, dir_color, color::Fg(RgbColor::from_str(fc).as_color())),
-                            None         => format_path(path, dir_color, fifo_color),
-                    }
-                } else if filetype.is_socket() {
-                    match config.socket {
-                        Some(ref sc) => format_path(path, dir_color, color::Fg(RgbColor::from_str(sc).as_color())),
-                        None         => format_path(path, dir_color, socket_color),
-                    }    
-                } else if filetype.is_dir() {
-                    match config.dir {
-                        Some(ref dc) => format_dir(path, color::Fg(RgbColor::from_str(dc).as_color())),
-                        None => format_dir(path, dir_color),
-                    }
-                } else {
-                    format!("{}", get_path_name(path))
-                }
-            },
-    },
-    None => { 
-            if filetype.is_file(){
-                format_path(path, dir_color, file_color)
-            } else if filetype.is_symlink() {
-                format_path(path, dir_color, symlink_color)
-            } else if filetype.is_block_device() {
-                format_path(path, dir_color, block_device_color)
-            } else if filetype.is_fifo() {
-                format_path(path, dir_color, fifo_color)
-            } else if filetype.is_socket() {
-                format_path(path, dir_color, socket_color)
-            } else if filetype.is_dir() {
-                format_dir(path, dir_color)
-            } else {
-                format!("{}", get_path_name(path))
-            }        
-        },
+    if !has_configured_colors(color_config) {
+        if let Some(painted) = format_ls_colors(display, path, filetype) {
+            return painted;
+        }
+    }
+
+    let palette = palette::of(color_config);
+    match palette.color_for(path, filetype) {
+        Some(color) if filetype.is_dir() => format_dir(display, color),
+        Some(color)                      => format_path(display, palette.prefix, color),
+        None                             => default_format,
     }
 }
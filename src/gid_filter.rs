@@ -1,5 +1,6 @@
 use filter;
 use filter::Filter;
+use filter::Search;
 use walkdir::DirEntry;
 use std::os::unix::fs::MetadataExt;
 use std::process;
@@ -11,12 +12,12 @@ pub struct GidFilter {
 
 impl GidFilter {
     pub fn new(comp_op: filter::CompOp, gid: u32) -> GidFilter {
-        GidFilter{comp_op: comp_op, gid: gid}
+        GidFilter{comp_op, gid}
     }
 }
 
 impl Filter for GidFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         match self.comp_op {
             filter::CompOp::Equal => self.gid == dir_entry.metadata().unwrap().gid(),
             filter::CompOp::Unequal => self.gid != dir_entry.metadata().unwrap().gid(),
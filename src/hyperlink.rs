@@ -0,0 +1,106 @@
+//! Clickable paths
+//!
+//! Many terminals open `file://` links, which are written with the OSC 8 escape
+//! sequence around the displayed text. The text stays as it is, only the target
+//! of the link is the canonical path of the file.
+
+use libc;
+use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum HyperlinkMode {
+    /// Links if colors are printed and the terminal is known to support them.
+    Auto,
+    Always,
+    Never,
+}
+
+impl HyperlinkMode {
+    pub fn from_name(name: &str) -> Option<HyperlinkMode> {
+        match name {
+            "auto"   => Some(HyperlinkMode::Auto),
+            "always" => Some(HyperlinkMode::Always),
+            "never"  => Some(HyperlinkMode::Never),
+            _        => None,
+        }
+    }
+}
+
+thread_local! {
+    static HOSTNAME: String = hostname();
+}
+
+/// Guesses from the environment whether the terminal supports OSC 8.
+pub fn terminal_supports() -> bool {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let vte_version = var("VTE_VERSION").parse::<u32>().unwrap_or(0);
+    let term_programs = ["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"];
+    let terms = ["xterm-kitty", "foot", "alacritty", "xterm-ghostty", "wezterm"];
+    vte_version >= 5000
+        || term_programs.contains(&var("TERM_PROGRAM").as_str())
+        || terms.iter().any(|t| var("TERM").starts_with(t))
+        || env::var("WT_SESSION").is_ok()
+        || env::var("KITTY_WINDOW_ID").is_ok()
+}
+
+fn hostname() -> String {
+    let mut buffer = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    buffer[buffer.len() - 1] = 0;
+    unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// Percent-encodes all bytes of a path except the unreserved characters and `/`.
+fn encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Turns `text` into a link to `path`, if `enabled`.
+pub fn wrap(path: &Path, text: String, enabled: bool) -> String {
+    if !enabled {
+        return text;
+    }
+    let target = match fs::canonicalize(path) {
+        Ok(p)  => p,
+        Err(_) => match env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_)  => return text,
+        },
+    };
+    HOSTNAME.with(|host| format!("\x1b]8;;file://{}{}\x1b\\{}\x1b]8;;\x1b\\", host, encode(&target), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, wrap};
+    use std::path::Path;
+
+    #[test]
+    fn links_only_if_enabled() {
+        assert_eq!(wrap(Path::new("/a b"), String::from("a b"), false), "a b");
+        let link = wrap(Path::new("/a b"), String::from("a b"), true);
+        assert!(link.starts_with("\x1b]8;;file://"));
+        assert!(link.ends_with("/a%20b\x1b\\a b\x1b]8;;\x1b\\"));
+    }
+
+    #[test]
+    fn encodes_reserved_bytes() {
+        assert_eq!(encode(Path::new("/tmp/a b#c%.txt")), "/tmp/a%20b%23c%25.txt");
+    }
+}
@@ -5,6 +5,9 @@
 //!   - [ ] '(' and ')' in filters
 //!
 
+// older versions of serde_derive put the derived impls into a constant
+#![allow(non_local_definitions)]
+
 #[macro_use]
 extern crate nom;
 extern crate regex;
@@ -17,10 +20,14 @@ extern crate liquid;
 extern crate colored;
 extern crate termion;
 extern crate libc;
+extern crate glob;
+extern crate unicode_width;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+use std::collections::HashMap;
+
 pub mod query;
 pub mod filter;
 pub mod parser;
@@ -33,10 +40,29 @@ pub mod time_filter;
 pub mod filetype_filter;
 pub mod uid_filter;
 pub mod gid_filter;
+pub mod command_filter;
+pub mod roots;
+pub mod executor;
+pub mod action;
+pub mod template_filters;
+pub mod builtin;
+pub mod template_context;
+pub mod command_attribute;
+pub mod output;
+pub mod table;
+pub mod tree;
+pub mod summary;
+pub mod ls_colors;
+pub mod palette;
+pub mod hyperlink;
+pub mod paths;
+pub mod timestamps;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub color : Option<ColorConfig>,
+    /// Named format templates, which can be selected with `--format NAME`.
+    pub formats: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -48,6 +74,15 @@ pub struct ColorConfig {
     socket: Option<String>,
     device: Option<String>,
     symlink: Option<String>,
+    executable: Option<String>,
+    broken_symlink: Option<String>,
+    /// Colors by extension, e.g. `rs = "DEA584"` in `[color.extension]`.
+    extension: Option<HashMap<String, String>>,
+    /// Colors by mime type, e.g. `"image/*" = "B48EAD"` in `[color.mimetype]`.
+    mimetype: Option<HashMap<String, String>>,
+    /// The parsed colors, see `palette::prepare`.
+    #[serde(skip_deserializing)]
+    palette: Option<palette::Palette>,
 }
 
 
@@ -0,0 +1,180 @@
+//! Colors from the `LS_COLORS` environment variable
+//!
+//! If no color is configured in the config file, names are colored like `ls` does
+//! it. `LS_COLORS` is a colon separated list of `key=SGR` pairs, where the key is
+//! either a file type like `di` or `ex` or a suffix pattern like `*.tar`. The SGR
+//! codes (`01;31`) may contain attributes like bold or underline and are used as is.
+//!
+//! As in `ls`, setuid, setgid and executable files take precedence over suffixes,
+//! and suffixes are only considered for regular files.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+pub struct LsColors {
+    /// Codes of the file type keys like `di`.
+    types: Vec<(String, String)>,
+    /// Suffixes without the leading `*` and their codes.
+    suffixes: Vec<(String, String)>,
+}
+
+thread_local! {
+    static LS_COLORS: Option<LsColors> = env::var("LS_COLORS").ok().map(|v| LsColors::parse(&v));
+}
+
+impl LsColors {
+    pub fn parse(ls_colors: &str) -> LsColors {
+        let mut types = Vec::new();
+        let mut suffixes = Vec::new();
+        for entry in ls_colors.split(':') {
+            let mut parts = entry.splitn(2, '=');
+            let key = parts.next().unwrap_or("");
+            let code = match parts.next() {
+                Some(c) => c,
+                None    => continue,
+            };
+            if let Some(suffix) = key.strip_prefix('*') {
+                suffixes.push((String::from(suffix), String::from(code)));
+            } else if !key.is_empty() {
+                types.push((String::from(key), String::from(code)));
+            }
+        }
+        LsColors{types, suffixes}
+    }
+
+    fn type_code(&self, key: &str) -> Option<&str> {
+        // later entries override earlier ones, like in ls
+        self.types.iter().rev().find(|&(k, _)| k == key).map(|(_, c)| c.as_str())
+    }
+
+    fn suffix_code(&self, name: &str) -> Option<&str> {
+        let exact = self.suffixes.iter().rev().find(|&(s, _)| name.ends_with(s.as_str()));
+        let lower_name = name.to_lowercase();
+        let any_case = || self.suffixes.iter().rev().find(|&(s, _)| lower_name.ends_with(&s.to_lowercase()));
+        exact.or_else(any_case).map(|(_, c)| c.as_str())
+    }
+
+    /// Returns the SGR codes for a path, or None if it should not be colored.
+    pub fn code(&self, path: &Path, file_type: fs::FileType) -> Option<&str> {
+        let mode = fs::symlink_metadata(path).map(|m| m.permissions().mode()).unwrap_or(0);
+        let code = if file_type.is_dir() {
+            if mode & 0o1002 == 0o1002 {
+                self.type_code("tw").or_else(|| self.type_code("di"))
+            } else if mode & 0o002 != 0 {
+                self.type_code("ow").or_else(|| self.type_code("di"))
+            } else if mode & 0o1000 != 0 {
+                self.type_code("st").or_else(|| self.type_code("di"))
+            } else {
+                self.type_code("di")
+            }
+        } else if file_type.is_symlink() {
+            match fs::metadata(path) {
+                Err(_)                                       => self.type_code("or").or_else(|| self.type_code("ln")),
+                Ok(ref m) if self.type_code("ln") == Some("target") => return self.code_of_target(path, m.file_type()),
+                Ok(_)                                        => self.type_code("ln"),
+            }
+        } else if file_type.is_fifo() {
+            self.type_code("pi")
+        } else if file_type.is_socket() {
+            self.type_code("so")
+        } else if file_type.is_block_device() {
+            self.type_code("bd")
+        } else if file_type.is_char_device() {
+            self.type_code("cd")
+        } else {
+            let suffix = || path.file_name().and_then(|n| self.suffix_code(&n.to_string_lossy()));
+            let special = if mode & 0o4000 != 0 {
+                self.type_code("su")
+            } else if mode & 0o2000 != 0 {
+                self.type_code("sg")
+            } else if mode & 0o111 != 0 {
+                self.type_code("ex")
+            } else {
+                None
+            };
+            special.or_else(suffix).or_else(|| self.type_code("fi"))
+        };
+        // 0 only resets the colors, which is the same as no color at all
+        code.and_then(|c| if c.is_empty() || c.trim_start_matches('0').is_empty() { None } else { Some(c) })
+    }
+
+    fn code_of_target(&self, path: &Path, target_type: fs::FileType) -> Option<&str> {
+        match fs::canonicalize(path) {
+            Ok(target) if !target_type.is_symlink() => self.code(&target, target_type),
+            _                                       => None,
+        }
+    }
+}
+
+/// Colors a name with the codes `LS_COLORS` has for `path`.
+/// Returns None if `LS_COLORS` is not set.
+pub fn paint(name: &str, path: &Path, file_type: fs::FileType) -> Option<String> {
+    LS_COLORS.with(|l| l.as_ref().map(|l| match l.code(path, file_type) {
+        Some(code) => format!("\x1b[{}m{}\x1b[0m", code, name),
+        None       => String::from(name),
+    }))
+}
+
+/// Colors the parent directory of a path with the directory codes, like `fd` does it.
+pub fn paint_directory(name: &str) -> Option<String> {
+    LS_COLORS.with(|l| l.as_ref().map(|l| match l.type_code("di") {
+        Some(code) => format!("\x1b[{}m{}\x1b[0m", code, name),
+        None       => String::from(name),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LsColors;
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::PathBuf;
+    use std::process;
+
+    #[test]
+    fn parses_types_and_suffixes() {
+        let colors = LsColors::parse("di=01;34:ln=target:*.tar=01;31:bogus:*.TXT=33:di=36:");
+        assert_eq!(colors.type_code("di"), Some("36"));
+        assert_eq!(colors.type_code("ln"), Some("target"));
+        assert_eq!(colors.type_code("ex"), None);
+        assert_eq!(colors.suffix_code("a.tar"), Some("01;31"));
+        assert_eq!(colors.suffix_code("notes.txt"), Some("33"));
+        assert_eq!(colors.suffix_code("a.tar.gz"), None);
+    }
+
+    #[test]
+    fn colors_like_ls() {
+        let dir = ::std::env::temp_dir().join(format!("frust-ls-colors-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, mode: u32| {
+            let path = dir.join(name);
+            fs::write(&path, "").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            path
+        };
+        let code = |path: &PathBuf, colors: &LsColors| {
+            let file_type = fs::symlink_metadata(path).unwrap().file_type();
+            colors.code(path, file_type).map(String::from)
+        };
+        let archive = file("a.tar", 0o644);
+        let script = file("run.tar", 0o755);
+        let plain = file("plain", 0o644);
+        symlink("missing", dir.join("broken")).unwrap();
+        symlink("a.tar", dir.join("link")).unwrap();
+
+        let colors = LsColors::parse("di=01;34:ex=01;32:fi=0:or=31:*.tar=01;31");
+        assert_eq!(code(&dir, &colors), Some(String::from("01;34")));
+        assert_eq!(code(&archive, &colors), Some(String::from("01;31")));
+        // executables take precedence over suffixes
+        assert_eq!(code(&script, &colors), Some(String::from("01;32")));
+        // a reset is no color
+        assert_eq!(code(&plain, &colors), None);
+        assert_eq!(code(&dir.join("broken"), &colors), Some(String::from("31")));
+        let targets = LsColors::parse("ln=target:*.tar=01;31");
+        assert_eq!(code(&dir.join("link"), &targets), Some(String::from("01;31")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -16,9 +16,13 @@ extern crate serde;
 use regex::Regex;
 use clap::{App, Arg};
 use frustlib::query::Query;
+use frustlib::output::Format;
 use std::fs::File;
 use std::io::prelude::*;
 use frustlib::Config;
+use frustlib::palette;
+use frustlib::hyperlink::HyperlinkMode;
+use frustlib::paths::PathStyle;
 use std::env;
 use std::process;
 
@@ -44,16 +48,23 @@ fn main() {
         .arg(Arg::with_name("QUERY")
              .help(r#"Find files according to the query the directory tree.
 [attributes] from [directories] where [conditions] exec [command];
+[attributes] from [directories] where [conditions] format '[template]';
+Use 'ok' instead of 'exec' to confirm each command on the terminal.
+Use '-' as directory to read the search roots from stdin.
 Currently supported attributes and comparison operators: 
   * name (==, ~)
   * basename (==, ~)
   * size (==, >, >=, <=, <)
   * mtime (==, >, >=, <=, <)
   * atime (==, >, >=, <=, <)
-  * ctime (==, >, >=, <=, <)
+  * ctime (==, >, >=, <=, <), the last status change
+  * btime, birthtime (==, >, >=, <=, <), the creation time
   * inode (==)
   * filetype (==)
   * mimetype (==)
+  * abspath, relpath, realpath (==, ~)
+  * cmd('command') (==, !=, ~, >, >=, <=, <), the output of a command
+Attributes can be renamed with 'as', e.g. cmd('wc -l') as lines.
                    "#)
              .index(1)
              .required(false)
@@ -96,6 +107,97 @@ Currently supported attributes and comparison operators:
 			 .required(false)
 			 .takes_value(false)
 			)
+		.arg(Arg::with_name("from-file")
+			 .short("f")
+			 .long("from-file")
+			 .help("Read the search roots from FILE instead of the from clause. Paths are NUL- or newline-separated, '-' reads from stdin.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("FILE")
+			)
+		.arg(Arg::with_name("jobs")
+			 .short("j")
+			 .long("jobs")
+			 .help("Number of exec commands which may run in parallel.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("N")
+			 .default_value("1")
+			 .validator(is_integer)
+			)
+		.arg(Arg::with_name("dry-run")
+			 .short("n")
+			 .long("dry-run")
+			 .help("Print the rendered commands of the exec clause instead of running them.")
+			 .required(false)
+			 .takes_value(false)
+			)
+		.arg(Arg::with_name("format")
+			 .long("format")
+			 .help("Output format of the attributes: plain, json (one array), ndjson (one object per line), csv, tsv or the name of a format template in the config file.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("FORMAT")
+			 .default_value("plain")
+			)
+		.arg(Arg::with_name("format-template")
+			 .long("format-template")
+			 .help("Print every entry by rendering TEMPLATE, e.g. '{{size | human}}\\t{{name}}'.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("TEMPLATE")
+			)
+		.arg(Arg::with_name("print0")
+			 .short("0")
+			 .long("print0")
+			 .help("End every printed entry with a NUL byte instead of a newline, implies --no-color.")
+			 .required(false)
+			 .takes_value(false)
+			)
+		.arg(Arg::with_name("hyperlink")
+			 .long("hyperlink")
+			 .help("Print names as links to the files: auto (with colors in supporting terminals), always or never.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("WHEN")
+			 .possible_values(&["auto", "always", "never"])
+			 .default_value("auto")
+			)
+		.arg(Arg::with_name("path-style")
+			 .long("path-style")
+			 .help("Print names relative to the working directory, absolute, canonical (symlinks resolved) or relative to their search root.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("STYLE")
+			 .possible_values(&["relative", "absolute", "canonical", "root-relative"])
+			)
+		.arg(Arg::with_name("tree")
+			 .long("tree")
+			 .help("Print the matches of each directory as a tree, instead of the attributes.")
+			 .required(false)
+			 .takes_value(false)
+			)
+		.arg(Arg::with_name("summary")
+			 .long("summary")
+			 .help("Print the number and size of the matches, the scanned entries and the elapsed time to stderr.")
+			 .required(false)
+			 .takes_value(false)
+			)
+		.arg(Arg::with_name("header")
+			 .long("header")
+			 .help("Start csv and tsv output with a row of the attribute names.")
+			 .required(false)
+			 .takes_value(false)
+			)
+		.arg(Arg::with_name("cmd-timeout")
+			 .long("cmd-timeout")
+			 .help("Seconds after which the command of a cmd('...') attribute is killed.")
+			 .required(false)
+			 .takes_value(true)
+			 .value_name("SECONDS")
+			 .default_value("10")
+			 .validator(is_integer)
+			)
 		.get_matches();
 
 
@@ -117,13 +219,19 @@ Currently supported attributes and comparison operators:
         },
     }
 
-	let config: Config = match toml::from_str(&config_contents) {
+	let mut config: Config = match toml::from_str(&config_contents) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Could not parse config {}", e);
             process::exit(1);
         },
     };
+    if let Some(ref mut color_config) = config.color {
+        if let Err(e) = palette::prepare(color_config) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
 
     let mut q = match matches.value_of("QUERY") {
         Some(query_inp) => {
@@ -137,10 +245,53 @@ Currently supported attributes and comparison operators:
         }
     };
 
+    if let Some(path_list) = matches.value_of("from-file") {
+        q.set_path_list(path_list);
+    }
+
+    let jobs = matches.value_of("jobs").unwrap().parse::<usize>().expect("Given number of jobs cannot be parsed to an integer!");
+    q.set_jobs(jobs);
+    q.set_dry_run(matches.is_present("dry-run"));
+    let cmd_timeout = matches.value_of("cmd-timeout").unwrap().parse::<u64>().expect("Given timeout cannot be parsed to an integer!");
+    q.set_command_timeout(cmd_timeout);
+    if !q.has_format_template() {
+        let format = matches.value_of("format").unwrap();
+        let named_format = config.formats.as_ref().and_then(|f| f.get(format));
+        if let Some(template) = matches.value_of("format-template") {
+            q.set_format_template(template);
+        } else if let Some(f) = Format::from_name(format) {
+            q.set_format(f);
+        } else if let Some(template) = named_format {
+            q.set_format_template(template);
+        } else {
+            eprintln!("Unknown format {}", format);
+            process::exit(1);
+        }
+    }
+    q.set_header(matches.is_present("header"));
+    q.set_print0(matches.is_present("print0"));
+    if matches.is_present("tree") && (q.has_format_template() || matches.value_of("format") != Some("plain")) {
+        eprintln!("--tree prints its own layout and cannot be combined with another format.");
+        process::exit(1);
+    }
+    q.set_tree(matches.is_present("tree"));
+    q.set_summary(matches.is_present("summary"));
+    if let Some(style) = matches.value_of("path-style") {
+        q.set_path_style(PathStyle::from_name(style).unwrap());
+    }
+    q.set_hyperlink(HyperlinkMode::from_name(matches.value_of("hyperlink").unwrap()).unwrap());
+
     let max_depth = matches.value_of("depth").unwrap().parse::<usize>().expect("Given depth cannot be parsed to an integer!");
     let machine_mode = matches.is_present("machine-readable");
     let same_device = matches.is_present("same-device");
     let ignore_hidden = matches.is_present("ignore-hidden");
     let color = !matches.is_present("no-color");
-    q.execute(max_depth, machine_mode, ignore_hidden, same_device, color, config.color);
+    let failed_commands = q.execute(max_depth, machine_mode, ignore_hidden, same_device, color, config.color);
+    if failed_commands > 0 {
+        eprintln!("{} commands failed.", failed_commands);
+        process::exit(1);
+    }
+    if q.has_root_errors() {
+        process::exit(1);
+    }
 }
@@ -1,4 +1,6 @@
 use filter::Filter;
+use filter::Search;
+use filter::Attribute;
 use walkdir::DirEntry;
 
 pub struct EqualNameFilter {
@@ -12,7 +14,7 @@ impl EqualNameFilter {
 }
 
 impl Filter for EqualNameFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         let attr = dir_entry.path().to_str();
         if attr.is_none() {
             eprintln!("UTF-8 Error");
@@ -33,7 +35,7 @@ impl EqualBasenameFilter {
 }
 
 impl Filter for EqualBasenameFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         let attr = dir_entry.file_name().to_str();
         if attr.is_none() {
             eprintln!("UTF-8 Error");
@@ -43,3 +45,24 @@ impl Filter for EqualBasenameFilter {
         self.string == attr.unwrap()
     }
 }
+
+/// Compares the paths which are derived from the path of an entry, like `abspath`.
+pub struct EqualPathFilter {
+    attribute: Attribute,
+    string: String,
+}
+
+impl EqualPathFilter {
+    pub fn new(attribute: Attribute, string: &str) -> EqualPathFilter {
+        EqualPathFilter{attribute, string: String::from(string)}
+    }
+}
+
+impl Filter for EqualPathFilter {
+    fn test(&self, dir_entry: &DirEntry, search: &Search) -> bool {
+        match self.attribute.derived_path(dir_entry, search.root) {
+            Some(path) => path.to_str().map(|p| p == self.string).unwrap_or(false),
+            None       => false,
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Output formats for the selected attributes
+//!
+//! Besides the plain comma separated lines, the attributes can be printed as JSON,
+//! either as one array (`json`) or as one object per line (`ndjson`). The objects
+//! are keyed by the names of the columns and the values keep their types: sizes
+//! and ids are numbers, times are RFC 3339 strings or, with `--machine-readable`,
+//! strings of the seconds since the epoch with nanoseconds, which a JSON number
+//! could not hold exactly. Paths which are not valid UTF-8 are written as arrays
+//! of their bytes.
+//!
+//! `csv` follows RFC 4180, fields are quoted if necessary and records end with CRLF.
+//! `tsv` escapes tabs, newlines and backslashes inside of fields with a backslash.
+//! Both may start with a header row of the column names.
+//!
+//! A format template is rendered with liquid for every entry, like the commands of
+//! the exec clause. `\t`, `\n`, `\0` and `\\` in it are replaced by the characters.
+
+use action;
+use timestamps;
+use chrono::{Local, TimeZone};
+use liquid::Template;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Format {
+    Plain,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    /// Rendered with a `FormatTemplate`.
+    Template,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "plain"  => Some(Format::Plain),
+            "json"   => Some(Format::Json),
+            "ndjson" => Some(Format::Ndjson),
+            "csv"    => Some(Format::Csv),
+            "tsv"    => Some(Format::Tsv),
+            _        => None,
+        }
+    }
+}
+
+/// A liquid template for the output of an entry.
+pub struct FormatTemplate {
+    pub template: Template,
+    variables: Vec<String>,
+}
+
+impl FormatTemplate {
+    pub fn new(format: &str) -> Result<FormatTemplate, String> {
+        let format = unescape(format);
+        Ok(FormatTemplate{template: action::parse_template(&format)?, variables: action::referenced_variables(&format)})
+    }
+
+    /// The names of all variables which may be referenced by the template.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+fn unescape(format: &str) -> String {
+    let mut unescaped = String::with_capacity(format.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t')  => unescaped.push('\t'),
+            Some('n')  => unescaped.push('\n'),
+            Some('0')  => unescaped.push('\0'),
+            Some('\\') => unescaped.push('\\'),
+            Some(e)    => { unescaped.push('\\'); unescaped.push(e); },
+            None       => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The value of an attribute before it is formatted.
+pub enum Field {
+    Text(String),
+    Path(OsString),
+    Number(u64),
+    Time(SystemTime),
+    /// The attribute is not available for the entry.
+    Missing,
+}
+
+/// Seconds since the epoch, negative for times before it.
+pub fn epoch_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d)  => d.as_secs() as i64,
+        Err(e) => {
+            let d = e.duration();
+            -(d.as_secs() as i64) - if d.subsec_nanos() > 0 { 1 } else { 0 }
+        },
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_value(field: &Field, machine_mode: bool) -> String {
+    match *field {
+        Field::Text(ref s)  => json_string(s),
+        Field::Path(ref p)  => match p.to_str() {
+            Some(s) => json_string(s),
+            None    => format!("[{}]", p.as_bytes().iter().map(|b| b.to_string()).collect::<Vec<String>>().join(",")),
+        },
+        Field::Number(n)    => n.to_string(),
+        Field::Time(t)      => {
+            // a string, so that parsers reading numbers as doubles keep the nanoseconds
+            if machine_mode {
+                json_string(&timestamps::epoch_string(t))
+            } else {
+                json_string(&Local.timestamp(epoch_seconds(t), 0).to_rfc3339())
+            }
+        },
+        Field::Missing      => String::from("null"),
+    }
+}
+
+/// Formats one entry as a JSON object keyed by the column names.
+pub fn json_object(keys: &[String], fields: &[Field], machine_mode: bool) -> String {
+    let members: Vec<String> = keys.iter()
+                                   .zip(fields.iter())
+                                   .map(|(k, f)| format!("{}:{}", json_string(k), json_value(f, machine_mode)))
+                                   .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+fn text_value(field: &Field, machine_mode: bool) -> String {
+    match *field {
+        Field::Text(ref s)  => s.clone(),
+        Field::Path(ref p)  => p.to_string_lossy().into_owned(),
+        Field::Number(n)    => n.to_string(),
+        Field::Time(t)      => {
+            if machine_mode {
+                timestamps::epoch_string(t)
+            } else {
+                Local.timestamp(epoch_seconds(t), 0).to_rfc3339()
+            }
+        },
+        Field::Missing      => String::new(),
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace("\"", "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+fn tsv_field(s: &str) -> String {
+    s.replace("\\", "\\\\").replace("\t", "\\t").replace("\n", "\\n").replace("\r", "\\r")
+}
+
+/// Formats one row of a table, without the line terminator.
+/// The fields are either the column names of the header or the values of an entry.
+pub fn table_row(format: Format, cells: &[String]) -> String {
+    match format {
+        Format::Tsv => cells.iter().map(|c| tsv_field(c)).collect::<Vec<String>>().join("\t"),
+        _           => cells.iter().map(|c| csv_field(c)).collect::<Vec<String>>().join(","),
+    }
+}
+
+/// Formats the values of an entry as text for `table_row`.
+pub fn text_values(fields: &[Field], machine_mode: bool) -> Vec<String> {
+    fields.iter().map(|f| text_value(f, machine_mode)).collect()
+}
+
+/// The line terminator of an entry in the given format.
+pub fn row_terminator(format: Format) -> &'static str {
+    match format {
+        Format::Csv => "\r\n",
+        _           => "\n",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_object, json_string, json_value, table_row, Field, Format};
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(json_string("a\tb\nc\r"), "\"a\\tb\\nc\\r\"");
+        assert_eq!(json_string("\u{1}\u{1f}\u{7f}ü"), "\"\\u0001\\u001f\u{7f}ü\"");
+    }
+
+    #[test]
+    fn keeps_the_types_of_fields() {
+        assert_eq!(json_value(&Field::Number(42), false), "42");
+        assert_eq!(json_value(&Field::Missing, false), "null");
+        assert_eq!(json_value(&Field::Text(String::from("dir")), false), "\"dir\"");
+        assert_eq!(json_value(&Field::Path(OsString::from("a/b")), false), "\"a/b\"");
+        // paths which are not UTF-8 are arrays of their bytes
+        assert_eq!(json_value(&Field::Path(OsString::from_vec(vec![b'a', 0xff])), false), "[97,255]");
+    }
+
+    #[test]
+    fn keeps_nanoseconds_of_machine_readable_times() {
+        let time = UNIX_EPOCH + Duration::new(1500000000, 123456789);
+        assert_eq!(json_value(&Field::Time(time), true), "\"1500000000.123456789\"");
+        assert_eq!(json_value(&Field::Time(UNIX_EPOCH - Duration::new(1, 500000000)), true), "\"-1.500000000\"");
+    }
+
+    #[test]
+    fn formats_objects() {
+        let keys = vec![String::from("name"), String::from("size")];
+        assert_eq!(json_object(&keys, &[Field::Path(OsString::from("x")), Field::Number(1)], false), "{\"name\":\"x\",\"size\":1}");
+    }
+
+    fn row(format: Format, cells: &[&str]) -> String {
+        table_row(format, &cells.iter().map(|c| String::from(*c)).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn csv_quotes_only_where_needed() {
+        assert_eq!(row(Format::Csv, &["a", "b c", ""]), "a,b c,");
+        assert_eq!(row(Format::Csv, &["a,b", "say \"hi\"", "two\nlines", "cr\r"]), "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\r\"");
+    }
+
+    #[test]
+    fn tsv_escapes_separators() {
+        assert_eq!(row(Format::Tsv, &["a b", "c,d"]), "a b\tc,d");
+        assert_eq!(row(Format::Tsv, &["tab\there", "new\nline", "cr\r", "back\\slash"]), "tab\\there\tnew\\nline\tcr\\r\tback\\\\slash");
+    }
+}
@@ -0,0 +1,328 @@
+//! Colors of the names
+//!
+//! The colors of the config file are either hex values (`A3BE8C` or `#A3BE8C`) or
+//! names like `red` or `bright_blue`. They are checked when the config is loaded.
+//! Hex values are written as truecolor escapes only if `COLORTERM` says that the
+//! terminal supports them, otherwise they are downgraded to the 256 color palette
+//! for `TERM`s ending in `256color`, and to the 16 basic colors for all others.
+//!
+//! Besides the file types, the config may color executables, broken symlinks and
+//! files by extension (`[color.extension]`) or mime type (`[color.mimetype]`).
+
+use ColorConfig;
+use mime_guess;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+const NAMES: [&str; 16] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    "bright_black", "bright_red", "bright_green", "bright_yellow", "bright_blue", "bright_magenta", "bright_cyan", "bright_white"];
+
+/// RGB values of the 16 basic colors in xterm.
+const BASIC_RGB: [(u8, u8, u8); 16] = [(0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205),
+    (0, 205, 205), (229, 229, 229), (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255),
+    (255, 0, 255), (0, 255, 255), (255, 255, 255)];
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guesses the colors the terminal supports from `COLORTERM` and `TERM`.
+    pub fn from_env() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.ends_with("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+thread_local! {
+    static DEPTH: ColorDepth = ColorDepth::from_env();
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Color {
+    /// The default color of the terminal.
+    Reset,
+    /// One of the 16 basic colors.
+    Basic(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    pub fn parse(color: &str) -> Result<Color, String> {
+        let name = color.trim().to_lowercase().replace('-', "_").replace("light_", "bright_");
+        if name == "reset" || name == "default" {
+            return Ok(Color::Reset);
+        }
+        if let Some(i) = NAMES.iter().position(|n| *n == name) {
+            return Ok(Color::Basic(i as u8));
+        }
+        let hex = color.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is neither a color name nor a hex value like #A3BE8C", color));
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(Color::Rgb(component(0), component(2), component(4)))
+    }
+
+    /// The escape code of the color for a terminal with the given capabilities.
+    pub fn escape(&self, depth: ColorDepth) -> String {
+        match (*self, depth) {
+            (Color::Reset, _)                          => String::from("\x1b[39m"),
+            (Color::Basic(n), _) if n < 8              => format!("\x1b[{}m", 30 + n),
+            (Color::Basic(n), _)                       => format!("\x1b[{}m", 90 + n - 8),
+            (Color::Rgb(r, g, b), ColorDepth::TrueColor) => format!("\x1b[38;2;{};{};{}m", r, g, b),
+            (Color::Rgb(r, g, b), ColorDepth::Ansi256) => format!("\x1b[38;5;{}m", ansi256(r, g, b)),
+            (Color::Rgb(r, g, b), ColorDepth::Ansi16)  => Color::Basic(nearest_basic(r, g, b)).escape(depth),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", DEPTH.with(|d| self.escape(*d)))
+    }
+}
+
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        // the gray ramp is finer than the gray of the color cube
+        return match r {
+            0..=7     => 16,
+            249..=255 => 231,
+            _         => 232 + ((r as u16 - 8) * 24 / 241) as u8,
+        };
+    }
+    let level = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn nearest_basic(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |&(br, bg, bb): &(u8, u8, u8)| {
+        let d = |x: u8, y: u8| (x as i32 - y as i32) * (x as i32 - y as i32);
+        d(r, br) + d(g, bg) + d(b, bb)
+    };
+    (0..16).min_by_key(|&i| distance(&BASIC_RGB[i])).unwrap_or(7) as u8
+}
+
+/// The colors of all file types, with the defaults of dircolors for the ones
+/// which are not configured.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Palette {
+    pub prefix: Color,
+    pub dir: Color,
+    pub file: Color,
+    pub fifo: Color,
+    pub socket: Color,
+    pub device: Color,
+    pub symlink: Color,
+    pub executable: Option<Color>,
+    pub broken_symlink: Option<Color>,
+    /// Colors of lowercase extensions.
+    extensions: HashMap<String, Color>,
+    /// Colors of mime types, where `image/*` matches all images.
+    mimetypes: Vec<(String, Color)>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette{prefix: Color::Basic(4), dir: Color::Basic(4), file: Color::Reset, fifo: Color::Basic(9),
+                socket: Color::Basic(5), device: Color::Basic(9), symlink: Color::Basic(6),
+                executable: None, broken_symlink: None, extensions: HashMap::new(), mimetypes: Vec::new()}
+    }
+}
+
+impl Palette {
+    /// Builds the palette of a config, the error names the key of an invalid color.
+    pub fn from_config(config: &ColorConfig) -> Result<Palette, String> {
+        let mut palette = Palette::default();
+        for (key, value) in config_entries(config) {
+            let color = parse_entry(key, value)?;
+            match key {
+                "prefix"         => palette.prefix = color,
+                "dir"            => palette.dir = color,
+                "file"           => palette.file = color,
+                "fifo"           => palette.fifo = color,
+                "socket"         => palette.socket = color,
+                "device"         => palette.device = color,
+                "symlink"        => palette.symlink = color,
+                "executable"     => palette.executable = Some(color),
+                _                => palette.broken_symlink = Some(color),
+            }
+        }
+        if let Some(ref extensions) = config.extension {
+            for (extension, value) in extensions {
+                let color = parse_entry(&format!("extension.{}", extension), value)?;
+                palette.extensions.insert(extension.trim_start_matches('.').to_lowercase(), color);
+            }
+        }
+        if let Some(ref mimetypes) = config.mimetype {
+            for (mimetype, value) in mimetypes {
+                let color = parse_entry(&format!("mimetype.\"{}\"", mimetype), value)?;
+                palette.mimetypes.push((mimetype.to_lowercase(), color));
+            }
+            // exact types before the wildcards
+            palette.mimetypes.sort_by_key(|(m, _)| m.ends_with("/*"));
+        }
+        Ok(palette)
+    }
+
+    /// The color of a file type, None for unknown types.
+    pub fn color_of(&self, filetype: fs::FileType) -> Option<Color> {
+        if filetype.is_file() {
+            Some(self.file)
+        } else if filetype.is_dir() {
+            Some(self.dir)
+        } else if filetype.is_symlink() {
+            Some(self.symlink)
+        } else if filetype.is_block_device() {
+            Some(self.device)
+        } else if filetype.is_fifo() {
+            Some(self.fifo)
+        } else if filetype.is_socket() {
+            Some(self.socket)
+        } else {
+            None
+        }
+    }
+
+    /// The color of a path, the most specific rule wins: a broken symlink, the
+    /// extension, the mime type, an exact one before a wildcard, an executable and
+    /// at last the file type.
+    pub fn color_for(&self, path: &Path, filetype: fs::FileType) -> Option<Color> {
+        if filetype.is_symlink() {
+            if let Some(color) = self.broken_symlink {
+                if fs::metadata(path).is_err() {
+                    return Some(color);
+                }
+            }
+            return Some(self.symlink);
+        }
+        if !filetype.is_file() {
+            return self.color_of(filetype);
+        }
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        if let Some(color) = extension.and_then(|e| self.extensions.get(&e)) {
+            return Some(*color);
+        }
+        if !self.mimetypes.is_empty() {
+            let mimetype = format!("{}", mime_guess::guess_mime_type(path)).to_lowercase();
+            let matches = |pattern: &str| match pattern.find("/*") {
+                Some(i) if pattern.len() == i + 2 => mimetype.starts_with(&pattern[..i + 1]),
+                _                                 => mimetype == pattern,
+            };
+            if let Some(&(_, color)) = self.mimetypes.iter().find(|&(m, _)| matches(m)) {
+                return Some(color);
+            }
+        }
+        if let Some(color) = self.executable {
+            let mode = fs::symlink_metadata(path).map(|m| m.permissions().mode()).unwrap_or(0);
+            if mode & 0o111 != 0 {
+                return Some(color);
+            }
+        }
+        Some(self.file)
+    }
+}
+
+fn parse_entry(key: &str, value: &str) -> Result<Color, String> {
+    Color::parse(value).map_err(|e| format!("Invalid color for color.{} in the config: {}", key, e))
+}
+
+fn config_entries(config: &ColorConfig) -> Vec<(&'static str, &str)> {
+    let entries = [("prefix", &config.prefix), ("dir", &config.dir), ("file", &config.file), ("fifo", &config.fifo),
+                   ("socket", &config.socket), ("device", &config.device), ("symlink", &config.symlink),
+                   ("executable", &config.executable), ("broken_symlink", &config.broken_symlink)];
+    entries.iter().filter_map(|&(k, v)| v.as_ref().map(|v| (k, v.as_str()))).collect()
+}
+
+/// Returns true if the config sets any color.
+pub fn has_colors(config: &ColorConfig) -> bool {
+    !config_entries(config).is_empty()
+        || config.extension.as_ref().map(|e| !e.is_empty()).unwrap_or(false)
+        || config.mimetype.as_ref().map(|m| !m.is_empty()).unwrap_or(false)
+}
+
+/// Checks all colors of the config and keeps the palette for the formatter.
+pub fn prepare(config: &mut ColorConfig) -> Result<(), String> {
+    config.palette = Some(Palette::from_config(config)?);
+    Ok(())
+}
+
+/// The palette of a config, see `prepare`.
+pub fn of<'a>(color_config: &'a Option<ColorConfig>) -> Cow<'a, Palette> {
+    match *color_config {
+        Some(ColorConfig{palette: Some(ref p), ..}) => Cow::Borrowed(p),
+        Some(ref config) => Cow::Owned(Palette::from_config(config).unwrap_or_default()),
+        None             => Cow::Owned(Palette::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ansi256, nearest_basic, Color, ColorDepth};
+
+    #[test]
+    fn parses_names_and_hex_values() {
+        assert_eq!(Color::parse("red"), Ok(Color::Basic(1)));
+        assert_eq!(Color::parse(" Bright_Blue "), Ok(Color::Basic(12)));
+        assert_eq!(Color::parse("light-cyan"), Ok(Color::Basic(14)));
+        assert_eq!(Color::parse("default"), Ok(Color::Reset));
+        assert_eq!(Color::parse("A3BE8C"), Ok(Color::Rgb(0xa3, 0xbe, 0x8c)));
+        assert_eq!(Color::parse("#a3be8c"), Ok(Color::Rgb(0xa3, 0xbe, 0x8c)));
+    }
+
+    #[test]
+    fn rejects_other_values() {
+        for color in &["", "#", "#FFF", "A3BE8", "#A3BE8C0", "GGGGGG", "reddish", "é3BE8C"] {
+            assert!(Color::parse(color).is_err(), "{}", color);
+        }
+    }
+
+    #[test]
+    fn downgrades_to_the_terminal() {
+        let color = Color::Rgb(255, 0, 0);
+        assert_eq!(color.escape(ColorDepth::TrueColor), "\x1b[38;2;255;0;0m");
+        assert_eq!(color.escape(ColorDepth::Ansi256), "\x1b[38;5;196m");
+        assert_eq!(color.escape(ColorDepth::Ansi16), "\x1b[91m");
+        assert_eq!(Color::Basic(4).escape(ColorDepth::TrueColor), "\x1b[34m");
+        assert_eq!(Color::Reset.escape(ColorDepth::Ansi16), "\x1b[39m");
+    }
+
+    #[test]
+    fn maps_to_the_256_color_palette() {
+        assert_eq!(ansi256(0, 0, 0), 16);
+        assert_eq!(ansi256(255, 255, 255), 231);
+        assert_eq!(ansi256(128, 128, 128), 243);
+        assert_eq!(ansi256(0, 0, 255), 21);
+    }
+
+    #[test]
+    fn finds_the_nearest_basic_color() {
+        assert_eq!(nearest_basic(200, 0, 0), 1);
+        assert_eq!(nearest_basic(250, 10, 10), 9);
+        assert_eq!(nearest_basic(20, 20, 20), 0);
+        assert_eq!(nearest_basic(0xa3, 0xbe, 0x8c), 8);
+    }
+}
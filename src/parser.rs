@@ -6,9 +6,14 @@
 //! I know this is limitting and I'm going to improve this in the future...
 
 use std::string::String;
+use std::process;
 use filter;
 use filter_tree;
 use query;
+use action;
+use builtin;
+use command_attribute;
+use roots;
 
 named!(komma, ws!(tag!(",")));
 named!(comp_op<filter::CompOp>, alt!(
@@ -35,7 +40,29 @@ named!(logic_op<filter_tree::LogicOp>,
 named!(from_keyword, ws!(tag!("from")));
 named!(where_keyword, ws!(tag!("where")));
 named!(exec_keyword, ws!(tag!("exec")));
-named!(attribute<filter::Attribute>, 
+named!(ok_keyword, ws!(tag!("ok")));
+named!(execdir_keyword, ws!(tag!("execdir")));
+named!(batch_keyword, re_bytes_find!("^batch\\s+"));
+named!(shell_keyword, re_bytes_find!("^sh\\s+"));
+fn command_attribute(command: &[u8]) -> filter::Attribute {
+    let command = String::from_utf8_lossy(command).into_owned();
+    if let Err(e) = command_attribute::check(&command) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    filter::Attribute::Command(command)
+}
+
+named!(cmd_attribute<filter::Attribute>, 
+       do_parse!(
+           re_bytes_find!("^cmd\\(") >>
+           command: quoted_string >>
+           tag!(")") >>
+           (command_attribute(command))
+       )
+   );
+named!(attribute<filter::Attribute>, alt!(ws!(cmd_attribute) | plain_attribute));
+named!(plain_attribute<filter::Attribute>, 
        do_parse!(
            tag: ws!(alt!( 
                       tag!("name")     => { |_| filter::Attribute::Name }
@@ -43,24 +70,46 @@ named!(attribute<filter::Attribute>,
                     | tag!("mtime")    => { |_| filter::Attribute::Mtime }
                     | tag!("ctime")    => { |_| filter::Attribute::Ctime }
                     | tag!("atime")    => { |_| filter::Attribute::Atime }
+                    | tag!("btime")    => { |_| filter::Attribute::Btime }
+                    | tag!("birthtime") => { |_| filter::Attribute::Btime }
                     | tag!("type") => { |_| filter::Attribute::Filetype }
                     | tag!("mimetype") => { |_| filter::Attribute::Mimetype }
                     | tag!("inode")    => { |_| filter::Attribute::Inode }
                     | tag!("basename") => { |_| filter::Attribute::Basename }
                     | tag!("uid")      => { |_| filter::Attribute::Uid }
                     | tag!("gid")      => { |_| filter::Attribute::Gid }
+                    | tag!("abspath")  => { |_| filter::Attribute::Abspath }
+                    | tag!("relpath")  => { |_| filter::Attribute::Relpath }
+                    | tag!("realpath") => { |_| filter::Attribute::Realpath }
                 )
                ) >>
                (tag)
            )
        );
 
-named!(directory<String>, 
+named!(plain_directory<roots::Directory>, 
        do_parse!(
            dir: re_bytes_find!("^/?([^/\0,; ]*(/)?)+") >>
-           (String::from_utf8_lossy(dir).into_owned())
+           (roots::Directory::Plain(String::from_utf8_lossy(dir).into_owned()))
+       )
+   );
+named!(single_quoted_directory<roots::Directory>, 
+       do_parse!(
+           tag!("'") >> 
+           dir: re_bytes_find!("^[^'\0]+") >>
+           tag!("'") >>
+           (roots::Directory::SingleQuoted(String::from_utf8_lossy(dir).into_owned()))
+       )
+   );
+named!(double_quoted_directory<roots::Directory>, 
+       do_parse!(
+           tag!("\"") >> 
+           dir: re_bytes_find!("^[^\"\0]+") >>
+           tag!("\"") >>
+           (roots::Directory::DoubleQuoted(String::from_utf8_lossy(dir).into_owned()))
        )
    );
+named!(directory<roots::Directory>, alt!(single_quoted_directory | double_quoted_directory | plain_directory));
 named!(num_paramter, re_bytes_find!("-?[0-9]+(\\.[0-9]*)?[a-zA-Z]*"));
 named!(str_paramter, 
        do_parse!(
@@ -70,7 +119,7 @@ named!(str_paramter,
            (pattern)
            )
        );
-named!(date_parameter, re_bytes_find!("(([0-9]{4}-[0-9]{2}-[0-9]{2})? ?[0-9]{1,2}:[0-9]{2})|([0-9]{4}-[0-9]{2}-[0-9]{2})"));
+named!(date_parameter, re_bytes_find!("^((([0-9]{4}-[0-9]{2}-[0-9]{2})? ?[0-9]{1,2}:[0-9]{2}(:[0-9]{2}(\\.[0-9]+)?)?)|([0-9]{4}-[0-9]{2}-[0-9]{2}))"));
 
 named!(parameter, alt!(str_paramter | date_parameter | num_paramter));
 
@@ -87,8 +136,22 @@ named!(filter<filter_tree::FilterTuple>,
      )
 );
 
-named!(directory_list<Vec<String>>, separated_list!(komma, directory));
-named!(attribute_list<Vec<filter::Attribute>>, separated_list!(komma, attribute));
+named!(directory_list<Vec<roots::Directory>>, separated_list!(komma, directory));
+named!(alias<String>, 
+       do_parse!(
+           ws!(re_bytes_find!("^as\\s")) >>
+           name: ws!(re_bytes_find!("^[A-Za-z_][A-Za-z0-9_]*")) >>
+           (String::from_utf8_lossy(name).into_owned())
+       )
+   );
+named!(column<filter::Column>, 
+       do_parse!(
+           attr: attribute >>
+           alias: opt!(alias) >>
+           (filter::Column::new(attr, alias))
+       )
+   );
+named!(column_list<Vec<filter::Column>>, separated_list!(komma, column));
 
 named!(filter_expr<filter_tree::FilterTree>,   
            do_parse!(
@@ -103,23 +166,35 @@ named!(filter_expr<filter_tree::FilterTree>,
            )
        );
 
-named!(command<String>, 
+named!(command<Result<action::CommandTemplate, String>>, 
        do_parse!(
        cmd: re_bytes_find!("[^;]+") >>
-        (String::from_utf8_lossy(cmd).into_owned())
+        (action::CommandTemplate::words(&String::from_utf8_lossy(cmd)))
+    )
+);
+named!(quoted_string, alt!(
+        delimited!(tag!("'"), re_bytes_find!("^[^']*"), tag!("'"))
+        | delimited!(tag!("\""), re_bytes_find!("^[^\"]*"), tag!("\""))
+    )
+);
+named!(shell_command<Result<action::CommandTemplate, String>>, 
+       do_parse!(
+       shell_keyword >>
+       script: quoted_string >>
+        (action::CommandTemplate::shell(&String::from_utf8_lossy(script)))
     )
 );
 
-named!(select_part<Option<Vec<filter::Attribute>>>,
+named!(select_part<Option<Vec<filter::Column>>>,
        opt!(
            do_parse!(
-               attrs: attribute_list >>
+               attrs: column_list >>
                (attrs)
            )
        )
    );
 
-named!(from_part<Option<Vec<String>>>,
+named!(from_part<Option<Vec<roots::Directory>>>,
        opt!(
            do_parse!(
                from_keyword >>
@@ -139,21 +214,119 @@ named!(where_part<Option<filter_tree::FilterTree>>,
        )
    );
 
-named!(exec_part<Option<String>>,
+named!(exec_action<Result<action::Action, String>>,
+       do_parse!(
+           exec_keyword >>
+           batch: opt!(batch_keyword) >>
+           cmd : alt!(shell_command | command) >>
+           (cmd.map(|c| match batch {
+               Some(_) => action::Action::Batch(c),
+               None    => action::Action::Exec(c),
+           }))
+       )
+   );
+
+named!(execdir_action<Result<action::Action, String>>,
+       do_parse!(
+           execdir_keyword >>
+           cmd : alt!(shell_command | command) >>
+           (cmd.map(action::Action::ExecDir))
+       )
+   );
+
+named!(ok_action<Result<action::Action, String>>,
+       do_parse!(
+           ok_keyword >>
+           cmd : alt!(shell_command | command) >>
+           (cmd.map(action::Action::Confirm))
+       )
+   );
+
+fn destination(template: &[u8]) -> Result<action::PathTemplate, String> {
+    action::PathTemplate::new(&String::from_utf8_lossy(template))
+}
+
+named!(builtin_part<Result<action::Action, String>>, alt!(
+          ws!(tag!("delete")) => { |_| Ok(action::Action::Builtin(builtin::Builtin::Delete)) }
+        | ws!(tag!("touch"))  => { |_| Ok(action::Action::Builtin(builtin::Builtin::Touch)) }
+        | do_parse!(
+              ws!(tag!("move")) >> ws!(tag!("to")) >>
+              dest: quoted_string >>
+              (destination(dest).map(|d| action::Action::Builtin(builtin::Builtin::Move(d))))
+          )
+        | do_parse!(
+              ws!(tag!("copy")) >> ws!(tag!("to")) >>
+              dest: quoted_string >>
+              (destination(dest).map(|d| action::Action::Builtin(builtin::Builtin::Copy(d))))
+          )
+        | do_parse!(
+              ws!(tag!("chmod")) >>
+              mode: re_bytes_find!("^[0-9]+") >>
+              (builtin::Builtin::chmod(&String::from_utf8_lossy(mode)).map(action::Action::Builtin))
+          )
+        | do_parse!(
+              ws!(tag!("chown")) >>
+              owner: re_bytes_find!("^[^;\\s]+") >>
+              (builtin::Builtin::chown(&String::from_utf8_lossy(owner)).map(action::Action::Builtin))
+          )
+    )
+);
+
+named!(exec_part<Option<Result<action::Action, String>>>, opt!(alt!(execdir_action | exec_action | ok_action | builtin_part)));
+
+named!(format_part<Option<String>>,
        opt!(
            do_parse!(
-               exec_keyword >>
-               cmd : command >>
-               (cmd)
+               ws!(re_bytes_find!("^format\\s")) >>
+               format: ws!(quoted_string) >>
+               (String::from_utf8_lossy(format).into_owned())
            )
        )
    );
 
-named!(pub query<query::Query>, do_parse!(
+// the query, or the error of a template or built-in action, which is only noticed
+// after the syntax has been parsed
+named!(pub query<Result<query::Query, String>>, do_parse!(
         attributes: select_part >> 
         directories: from_part >>
         filters: where_part >>
+        format: format_part >>
         command: exec_part >>
-        (query::Query::new(attributes, directories, filters, command))
+        (match command {
+            Some(Err(e)) => Err(e),
+            Some(Ok(c))  => Ok(query::Query::new(attributes, directories, filters, format, Some(c))),
+            None         => Ok(query::Query::new(attributes, directories, filters, format, None)),
+        })
         )
     );
+
+#[cfg(test)]
+mod tests {
+    use super::date_parameter;
+    use nom::IResult;
+
+    fn date(input: &str) -> Option<(&str, &str)> {
+        match date_parameter(input.as_bytes()) {
+            IResult::Done(rest, matched) => Some((::std::str::from_utf8(matched).unwrap(), ::std::str::from_utf8(rest).unwrap())),
+            _                            => None,
+        }
+    }
+
+    #[test]
+    fn date_parameter_takes_all_forms() {
+        assert_eq!(date("2017-01-01;"), Some(("2017-01-01", ";")));
+        assert_eq!(date("17:05;"), Some(("17:05", ";")));
+        assert_eq!(date("2017-01-01 17:05;"), Some(("2017-01-01 17:05", ";")));
+        assert_eq!(date("2017-01-01 17:05:09;"), Some(("2017-01-01 17:05:09", ";")));
+        assert_eq!(date("2017-01-01 17:05:09.123456789 and"), Some(("2017-01-01 17:05:09.123456789", " and")));
+        assert_eq!(date("7:05:09.25;"), Some(("7:05:09.25", ";")));
+    }
+
+    #[test]
+    fn date_parameter_stops_at_incomplete_parts() {
+        assert_eq!(date("17:05:9;"), Some(("17:05", ":9;")));
+        assert_eq!(date("17:05:09.;"), Some(("17:05:09", ".;")));
+        assert_eq!(date("size"), None);
+        assert_eq!(date("x 17:05"), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! Styles of printed paths
+//!
+//! walkdir yields paths prefixed with the search root as it was written in the
+//! query. `--path-style` prints them instead relative to the working directory,
+//! as absolute paths, with all symlinks resolved or relative to their search root.
+//! The same forms are available as the attributes `abspath`, `realpath` and `relpath`.
+
+use template_filters;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum PathStyle {
+    /// Relative to the working directory.
+    Relative,
+    /// Absolute, but without resolving symlinks.
+    Absolute,
+    /// Absolute with all symlinks resolved.
+    Canonical,
+    /// Relative to the search root.
+    RootRelative,
+}
+
+impl PathStyle {
+    pub fn from_name(name: &str) -> Option<PathStyle> {
+        match name {
+            "relative"      => Some(PathStyle::Relative),
+            "absolute"      => Some(PathStyle::Absolute),
+            "canonical"     => Some(PathStyle::Canonical),
+            "root-relative" => Some(PathStyle::RootRelative),
+            _               => None,
+        }
+    }
+}
+
+/// Joins a path to the working directory and removes `.` and `..` lexically.
+/// Symlinks are not looked at, so `link/..` becomes the directory containing
+/// `link` rather than the parent of its target. `canonical` resolves them.
+pub fn absolute(path: &Path) -> PathBuf {
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => { normalized.pop(); },
+            c => normalized.push(c.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Resolves all symlinks, broken ones are made absolute only.
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| absolute(path))
+}
+
+/// The path relative to the working directory.
+pub fn relative(path: &Path) -> PathBuf {
+    match env::current_dir() {
+        Ok(cwd) => template_filters::relative_path(&absolute(path), &cwd),
+        Err(_)  => path.to_path_buf(),
+    }
+}
+
+/// The path relative to the search root it was found in, `.` for the root itself.
+/// A listed path, whose root is the working directory `.`, may be outside of it.
+pub fn root_relative(path: &Path, root: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        Ok(_)                              => PathBuf::from("."),
+        Err(_)                             => template_filters::relative_path(&absolute(path), &absolute(root)),
+    }
+}
+
+pub fn styled(path: &Path, style: PathStyle, root: &Path) -> PathBuf {
+    match style {
+        PathStyle::Relative     => relative(path),
+        PathStyle::Absolute     => absolute(path),
+        PathStyle::Canonical    => canonical(path),
+        PathStyle::RootRelative => root_relative(path, root),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{absolute, root_relative};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn makes_paths_relative_to_their_root() {
+        assert_eq!(root_relative(Path::new("/tmp/a/b.txt"), Path::new("/tmp")), PathBuf::from("a/b.txt"));
+        assert_eq!(root_relative(Path::new("/tmp"), Path::new("/tmp")), PathBuf::from("."));
+        assert_eq!(root_relative(Path::new("./src/main.rs"), Path::new(".")), PathBuf::from("src/main.rs"));
+    }
+
+    #[test]
+    fn makes_listed_paths_relative_to_the_working_directory() {
+        assert_eq!(root_relative(Path::new("src/main.rs"), Path::new(".")), PathBuf::from("src/main.rs"));
+        assert_eq!(root_relative(Path::new("../x"), Path::new(".")), PathBuf::from("../x"));
+        assert_eq!(root_relative(&absolute(Path::new("src")), Path::new(".")), PathBuf::from("src"));
+    }
+}
@@ -1,4 +1,5 @@
 use filter;
+use filter::Search;
 use filter_tree;
 use ColorConfig;
 use formatter;
@@ -7,27 +8,84 @@ use walkdir::WalkDir;
 use walkdir::DirEntry;
 use walkdir::WalkDirIterator;
 use parser;
+use roots;
+use executor;
+use executor::{Executor, Answer};
+use action;
+use action::Action;
+use template_context;
+use command_attribute::CommandAttributes;
+use output;
+use output::{Field, Format, FormatTemplate};
+use table::Table;
+use tree::Tree;
+use summary::Summary;
+use hyperlink;
+use hyperlink::HyperlinkMode;
+use paths;
+use paths::PathStyle;
+use timestamps;
+use template_filters;
+use builtin;
+use builtin::Builtin;
 use nom::IResult;
 use nom::Needed;
 use mime_guess;
-use liquid;
-use liquid::{Renderable, Context, Value};
-use termion::{is_tty};
+use liquid::{Context, Value};
+use termion::{is_tty, terminal_size};
+use std::ffi::OsString;
+use std::io;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::os::unix::fs::MetadataExt;
 use std::process;
-use std::process::Command;
+use std::mem;
+use std::cell::Cell;
+use std::time::Duration;
 
 fn stdout_is_tty() -> bool {
-    is_tty(&fs::File::create("/dev/stdout").unwrap())
+    is_tty(&io::stdout())
 }
 
 pub struct Query {
-    attributes: Vec<filter::Attribute>,
-    directories: Vec<String>,
+    attributes: Vec<filter::Column>,
+    directories: Vec<roots::Directory>,
     filters: filter_tree::FilterTree,
-    command: Option<String>,
+    command: Option<Action>,
+    path_list: Option<String>,
+    /// The directory of the from clause which is searched right now, everything
+    /// relative to the search root refers to it.
+    current_root: PathBuf,
+    executor: Executor,
+    confirm_all: bool,
+    quit: bool,
+    deleted_dirs: Vec<PathBuf>,
+    batch: Vec<OsString>,
+    batch_length: usize,
+    max_batch_length: usize,
     machine_mode: bool,
+    format: Format,
+    /// Number of entries printed so far.
+    records: usize,
+    header: bool,
+    print0: bool,
+    /// Layout of the aligned output on a terminal.
+    table: Option<Table>,
+    format_template: Option<FormatTemplate>,
+    tree_mode: bool,
+    /// Matches of the current search root in tree mode.
+    tree: Option<Tree>,
+    /// Counters of the walk, if `--summary` is given.
+    summary: Option<Summary>,
+    hyperlink_mode: HyperlinkMode,
+    /// Whether names are printed as links, decided by `execute` from `hyperlink_mode`.
+    hyperlinks: bool,
+    /// Form of the printed names, as walked if None.
+    path_style: Option<PathStyle>,
+    /// Number of directories of the from clause which matched nothing.
+    root_errors: usize,
+    /// Runs the commands of `cmd(...)` attributes.
+    commands: CommandAttributes,
 }
 
 fn is_hidden(entry: &DirEntry) -> bool {
@@ -38,19 +96,35 @@ fn is_hidden(entry: &DirEntry) -> bool {
 }
 
 impl Query {
-    pub fn new(attributes: Option<Vec<filter::Attribute>>, directories: Option<Vec<String>>, filters: Option<filter_tree::FilterTree>, command: Option<String>) -> Query{
-        let mut attr = attributes.unwrap_or(vec![filter::Attribute::Name]);
-        let dirs = directories.unwrap_or(vec![String::from(".")]);
+    pub fn new(attributes: Option<Vec<filter::Column>>, directories: Option<Vec<roots::Directory>>, filters: Option<filter_tree::FilterTree>, format: Option<String>, command: Option<Action>) -> Query{
+        let mut attr = attributes.unwrap_or(vec![filter::Column::new(filter::Attribute::Name, None)]);
+        let dirs = directories.unwrap_or(vec![roots::Directory::Plain(String::from("."))]);
         let filters = filters.unwrap_or(filter_tree::FilterTree::new(None, None, None));
-        if attr.len() == 0 {
-            attr.push(filter::Attribute::Name);
+        if attr.is_empty() {
+            attr.push(filter::Column::new(filter::Attribute::Name, None));
         }
-        Query{attributes: attr, directories: dirs, filters: filters, command: command, machine_mode: false}
+        let max_batch_length = match command {
+            Some(Action::Batch(ref c)) => executor::max_command_length(c.is_shell() || c.embeds_names()).saturating_sub(c.length()),
+            _                          => 0,
+        };
+        let mut query = Query{attributes: attr, directories: dirs, filters, command, path_list: None, current_root: PathBuf::new(), executor: Executor::new(), confirm_all: false, quit: false, deleted_dirs: Vec::new(),
+              batch: Vec::new(), batch_length: 0, max_batch_length, machine_mode: false,
+              format: Format::Plain, records: 0, header: false, print0: false, table: None, format_template: None,
+              tree_mode: false, tree: None, summary: None,
+              hyperlink_mode: HyperlinkMode::Auto, hyperlinks: false, path_style: None, root_errors: 0, commands: CommandAttributes::new()};
+        if let Some(format) = format {
+            query.set_format_template(&format);
+        }
+        query
     }
 
     pub fn parse(inp: &str) -> Query {
         match parser::query(inp.as_bytes()){
             IResult::Done(leftovers, q)     => {
+                let q = q.unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
                 if leftovers.len() > 1 {
                     eprintln!("Could not parse from here -> {}\nDid you make a typo?\n\n", String::from_utf8_lossy(leftovers).into_owned());
                     process::exit(1);
@@ -74,24 +148,225 @@ impl Query {
         }
     }
 
-    fn print_attributes(&self, entry: &DirEntry, color_config: &Option<ColorConfig>, color_mode: bool) {
-        let mut print_string = String::from("");
-        for attribute in &self.attributes {
-            let attr_str = match *attribute {
-                filter::Attribute::Name	    => formatter::format_name(entry, color_config, color_mode),
-                filter::Attribute::Basename	=> format!("{}", entry.file_name().to_str().unwrap()),
+    /// Replaces the directories of the from clause by the paths listed in `file`.
+    /// The paths are NUL- or newline-separated, `-` reads them from stdin.
+    pub fn set_path_list(&mut self, file: &str) {
+        self.path_list = Some(String::from(file));
+    }
+
+    /// Sets the number of exec commands which may run in parallel.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.executor.set_jobs(jobs);
+    }
+
+    /// Only prints the rendered commands instead of running them.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.executor.set_dry_run(dry_run);
+    }
+
+    /// Sets after how many seconds the command of a `cmd(...)` attribute is killed.
+    pub fn set_command_timeout(&mut self, seconds: u64) {
+        self.commands.set_timeout(Duration::from_secs(seconds));
+    }
+
+    /// Sets the format in which the attributes are printed.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Prints every entry by rendering a liquid template.
+    pub fn set_format_template(&mut self, format: &str) {
+        match FormatTemplate::new(format) {
+            Ok(t)  => self.format_template = Some(t),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            },
+        }
+        self.format = Format::Template;
+    }
+
+    /// Returns true if the query contains a format clause.
+    pub fn has_format_template(&self) -> bool {
+        self.format_template.is_some()
+    }
+
+    /// Prints the matches of each search root as a tree.
+    pub fn set_tree(&mut self, tree: bool) {
+        self.tree_mode = tree;
+    }
+
+    /// Prints counts and sizes of the matches to stderr after the walk.
+    pub fn set_summary(&mut self, summary: bool) {
+        self.summary = if summary { Some(Summary::new()) } else { None };
+    }
+
+    /// Sets when printed names are links to the files.
+    pub fn set_hyperlink(&mut self, mode: HyperlinkMode) {
+        self.hyperlink_mode = mode;
+    }
+
+    /// Sets the form in which names are printed.
+    pub fn set_path_style(&mut self, style: PathStyle) {
+        self.path_style = Some(style);
+    }
+
+    /// Starts the csv and tsv formats with a row of the column names.
+    pub fn set_header(&mut self, header: bool) {
+        self.header = header;
+    }
+
+    /// Ends every printed entry with a NUL byte instead of a newline.
+    pub fn set_print0(&mut self, print0: bool) {
+        self.print0 = print0;
+    }
+
+    /// Returns true if a directory of the from clause could not be expanded or matched nothing.
+    pub fn has_root_errors(&self) -> bool {
+        self.root_errors > 0
+    }
+
+    fn roots(&self) -> Vec<Result<roots::Root, String>> {
+        match self.path_list {
+            Some(ref file) => roots::load_path_list(file).into_iter().map(Ok).collect(),
+            None           => roots::expand(&self.directories),
+        }
+    }
+
+    fn print_attributes(&mut self, entry: &DirEntry, color_config: &Option<ColorConfig>, color_mode: bool) {
+        if let Some(ref mut tree) = self.tree {
+            tree.insert(entry);
+            return;
+        }
+        match self.format {
+            Format::Plain  => self.print_plain(entry, color_config, color_mode),
+            Format::Json   => {
+                let object = output::json_object(&self.column_names(), &self.fields(entry), self.machine_mode);
+                print!("{}{}", if self.records == 0 { "[\n" } else { ",\n" }, object);
+            },
+            Format::Ndjson => print!("{}{}", output::json_object(&self.column_names(), &self.fields(entry), self.machine_mode), self.terminator()),
+            Format::Template => {
+                let rendered = match self.format_template {
+                    Some(ref f) => self.setup_context(entry, f.variables()).map(|mut context| action::render(&f.template, &mut context)),
+                    None        => Ok(Ok(String::new())),
+                };
+                let rendered = match rendered {
+                    Ok(r)  => r.unwrap_or_else(|e| self.abort(&e)),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        if let Some(ref mut summary) = self.summary {
+                            summary.error();
+                        }
+                        return;
+                    },
+                };
+                print!("{}{}", rendered, self.terminator());
+            },
+            Format::Csv | Format::Tsv => {
+                let row = output::table_row(self.format, &output::text_values(&self.fields(entry), self.machine_mode));
+                print!("{}{}", row, self.terminator());
+            },
+        }
+        self.records += 1;
+    }
+
+    /// The end of a printed entry, NUL with `--print0`.
+    fn terminator(&self) -> &'static str {
+        if self.print0 {
+            "\0"
+        } else {
+            output::row_terminator(self.format)
+        }
+    }
+
+    /// Prints what precedes the first entry.
+    fn start_output(&self) {
+        if let Some(ref table) = self.table {
+            println!("{}", table.row(&self.column_names()));
+        }
+        match self.format {
+            Format::Csv | Format::Tsv if self.header => {
+                print!("{}{}", output::table_row(self.format, &self.column_names()), self.terminator());
+            },
+            _ => {},
+        }
+    }
+
+    /// Closes the output after the last entry.
+    fn finish_output(&self) {
+        if self.format == Format::Json {
+            println!("{}", if self.records == 0 { "[]" } else { "\n]" });
+        }
+    }
+
+    fn column_names(&self) -> Vec<String> {
+        self.attributes.iter().map(|c| c.name()).collect()
+    }
+
+    /// Collects the typed values of the selected attributes.
+    fn fields(&self, entry: &DirEntry) -> Vec<Field> {
+        let metadata = entry.metadata().ok();
+        let time = |t: Option<::std::io::Result<::std::time::SystemTime>>| match t {
+            Some(Ok(t)) => Field::Time(t),
+            _           => Field::Missing,
+        };
+        self.attributes.iter().map(|column| match column.attribute {
+            filter::Attribute::Name     => Field::Path(self.display_path(entry).into_os_string()),
+            filter::Attribute::Abspath | filter::Attribute::Relpath | filter::Attribute::Realpath => {
+                Field::Path(column.attribute.derived_path(entry, &self.current_root).unwrap_or_default().into_os_string())
+            },
+            filter::Attribute::Basename => Field::Path(entry.file_name().to_os_string()),
+            filter::Attribute::Size     => metadata.as_ref().map(|m| Field::Number(m.len())).unwrap_or(Field::Missing),
+            filter::Attribute::Mtime | filter::Attribute::Ctime | filter::Attribute::Atime | filter::Attribute::Btime => {
+                time(metadata.as_ref().map(|m| timestamps::of(&column.attribute, m)))
+            },
+            filter::Attribute::Filetype => Field::Text(formatter::format_filetype(entry.file_type(), true)),
+            filter::Attribute::Mimetype => Field::Text(formatter::format_mimetype(mime_guess::guess_mime_type(entry.path()), true)),
+            filter::Attribute::Inode    => Field::Number(entry.ino()),
+            filter::Attribute::Uid      => metadata.as_ref().map(|m| Field::Number(m.uid() as u64)).unwrap_or(Field::Missing),
+            filter::Attribute::Gid      => metadata.as_ref().map(|m| Field::Number(m.gid() as u64)).unwrap_or(Field::Missing),
+            filter::Attribute::Command(ref command) => match self.commands.output(entry, &self.current_root, command) {
+                Ok(o)  => Field::Text(o),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    Field::Missing
+                },
+            },
+        }).collect()
+    }
+
+    /// The path of an entry as it is printed for `name`.
+    fn display_path(&self, entry: &DirEntry) -> PathBuf {
+        match self.path_style {
+            Some(style) => paths::styled(entry.path(), style, &self.current_root),
+            None        => entry.path().to_path_buf(),
+        }
+    }
+
+    fn print_plain(&self, entry: &DirEntry, color_config: &Option<ColorConfig>, color_mode: bool) {
+        let cells = self.plain_cells(entry, color_config, color_mode);
+        match self.table {
+            Some(ref table) => println!("{}", table.row(&cells)),
+            None            => print!("{}{}", cells.join(","), self.terminator()),
+        }
+    }
+
+    fn plain_cells(&self, entry: &DirEntry, color_config: &Option<ColorConfig>, color_mode: bool) -> Vec<String> {
+        let mut cells = Vec::new();
+        for column in &self.attributes {
+            let attr_str = match column.attribute {
+                filter::Attribute::Name	    => formatter::format_name(entry, &self.display_path(entry), color_config, color_mode, self.hyperlinks),
+                filter::Attribute::Abspath | filter::Attribute::Relpath | filter::Attribute::Realpath => {
+                    column.attribute.derived_path(entry, &self.current_root).unwrap_or_default().to_string_lossy().into_owned()
+                },
+                filter::Attribute::Basename	=> entry.file_name().to_str().unwrap().to_string(),
                 filter::Attribute::Size	    => formatter::format_filesize(entry.metadata().unwrap().len(), self.machine_mode),
                 filter::Attribute::Mtime	=> formatter::format_systime(entry.metadata().unwrap().modified().unwrap(), self.machine_mode),
-                filter::Attribute::Ctime	=> {
-                    match entry.metadata().unwrap().created() {
+                filter::Attribute::Ctime | filter::Attribute::Atime | filter::Attribute::Btime => {
+                    match timestamps::of(&column.attribute, &entry.metadata().unwrap()) {
                         Ok(t)   => formatter::format_systime(t, self.machine_mode),
                         Err(_)  => String::from("N/A"),
                     }},
-                filter::Attribute::Atime    => {
-                    match entry.metadata().unwrap().accessed() {
-                        Ok(t)   => formatter::format_systime(t, self.machine_mode),
-                        Err(_)  => String::from("N/A"),
-                    }},                
                 filter::Attribute::Filetype	=> formatter::format_filetype(entry.metadata().unwrap().file_type(), self.machine_mode),
                 filter::Attribute::Mimetype	=> {
                     let filepath = format!("{}", entry.path().display());
@@ -100,132 +375,327 @@ impl Query {
                 filter::Attribute::Inode	=> format!("{}", entry.ino()),
                 filter::Attribute::Uid 	    => format!("{}", entry.metadata().unwrap().uid()),
                 filter::Attribute::Gid 	    => format!("{}", entry.metadata().unwrap().gid()),
+                filter::Attribute::Command(ref command) => {
+                    match self.commands.output(entry, &self.current_root, command) {
+                        Ok(o)   => o,
+                        Err(e)  => {
+                            eprintln!("{}", e);
+                            String::from("N/A")
+                        },
+                    }},
             };
-            if !print_string.is_empty() {
-                print_string.push(',');
-            }
-            print_string.push_str(attr_str.as_str());
+            cells.push(attr_str);
         }
-        println!("{}", print_string);
+        cells
+    }
+
+    /// The state of the search the filters may need.
+    fn search(&self) -> Search<'_> {
+        Search{root: &self.current_root, commands: &self.commands}
+    }
+
+    /// Sets up a context with the variables referenced by a template.
+    fn setup_context(&self, dir_entry: &DirEntry, variables: &[String]) -> Result<Context, String> {
+        template_context::setup_context(dir_entry, variables, &self.current_root, self.machine_mode)
     }
 
-    fn setup_context(&self, dir_entry: &DirEntry) -> Context {
-        // TODO does this need speed improvement? All attributes will be needed rarely...
+    fn setup_batch_context(&self) -> Context {
         let mut context = Context::new();
-        context.set_val("name", Value::Str(String::from( dir_entry.path().to_str().unwrap_or("N/A"))));
-        context.set_val("basename", Value::Str(String::from(dir_entry.file_name().to_str().unwrap_or("N/A"))));
-        context.set_val("size", Value::Str(formatter::format_filesize(dir_entry.metadata().unwrap().len(), self.machine_mode)));
-        context.set_val("mtime", Value::Str(formatter::format_systime(dir_entry.metadata().unwrap().modified().unwrap(), self.machine_mode)));
-        let ctime = match dir_entry.metadata().unwrap().created() {
-                Ok(t)   => formatter::format_systime(t, self.machine_mode),
-                Err(_)  => String::from("N/A"),
+        template_filters::register(&mut context);
+        // names which are not valid UTF-8 never get into a batch which embeds them
+        context.set_val("names", Value::Array(self.batch.iter().filter_map(|n| n.to_str()).map(|n| Value::Str(String::from(n))).collect()));
+        context
+    }
+
+    /// Ends frust after an error in the middle of the walk. The commands which
+    /// are still running are waited for, so that no child is left behind.
+    fn abort(&mut self, message: &str) -> ! {
+        eprintln!("{}", message);
+        self.executor.finish();
+        process::exit(1);
+    }
+
+    fn run_command(&mut self, dir_entry: &DirEntry) {
+        let built = match self.command {
+            None                       => return,
+            Some(Action::Batch(_))     => {
+                self.add_to_batch(dir_entry);
+                return;
+            },
+            Some(Action::Builtin(_))   => {
+                self.run_builtin(dir_entry);
+                return;
+            },
+            Some(Action::Exec(ref c))  => {
+                self.setup_context(dir_entry, c.variables()).map(|mut context| c.build(&mut context, &[]))
+            },
+            Some(Action::Confirm(ref c)) => {
+                self.setup_context(dir_entry, c.variables()).map(|mut context| c.build(&mut context, &[]))
+            },
+            Some(Action::ExecDir(ref c)) => {
+                let basename = match dir_entry.file_name().to_str() {
+                    Some(b)                                          => Ok(b),
+                    None if c.variables().iter().any(|v| v == "name") => Err(template_context::not_utf8(dir_entry.path())),
+                    None                                             => Ok(""),
+                };
+                basename.and_then(|basename| self.setup_context(dir_entry, c.variables()).map(|context| (basename, context))).map(|(basename, mut context)| {
+                    context.set_val("name", Value::Str(format!("./{}", basename)));
+                    let parent = match dir_entry.path().parent() {
+                        Some(p) if !p.as_os_str().is_empty() => p,
+                        _                                    => Path::new("."),
+                    };
+                    c.build(&mut context, &[]).map(|(mut command, description)| {
+                        command.current_dir(parent);
+                        (command, format!("cd {} && {}", template_filters::quote_if_needed(&parent.to_string_lossy()), description))
+                    })
+                })
+            },
         };
-        context.set_val("ctime", Value::Str(ctime));
+        let built = match built {
+            Ok(b)  => b,
+            Err(e) => {
+                self.executor.report_failure(&e);
+                return;
+            },
+        };
+        let (command, description) = built.unwrap_or_else(|e| self.abort(&e));
+        let confirm = match self.command {
+            Some(Action::Confirm(_)) => !self.confirm_all && !self.executor.is_dry_run(),
+            _                        => false,
+        };
+        if confirm {
+            match executor::ask(&description).unwrap_or_else(|e| self.abort(&e)) {
+                Answer::Yes  => {},
+                Answer::No   => return,
+                Answer::All  => self.confirm_all = true,
+                Answer::Quit => {
+                    self.quit = true;
+                    return;
+                },
+            }
+        }
+        self.executor.spawn(command, description);
+    }
 
-        let atime = match dir_entry.metadata().unwrap().accessed() {
-                Ok(t)   => formatter::format_systime(t, self.machine_mode),
-                Err(_)  => String::from("N/A"),
-            };
-        context.set_val("atime", Value::Str(atime));
-
-        context.set_val("type", Value::Str(formatter::format_filetype(dir_entry.metadata().unwrap().file_type(), self.machine_mode)));
-        context.set_val("mimetype", Value::Str(formatter::format_mimetype(mime_guess::guess_mime_type(dir_entry.path().to_str().unwrap_or("N/A")), self.machine_mode)));
-        context.set_val("inode", Value::Str(format!("{}", dir_entry.ino())));
-        return context;
-    }
-
-    fn run_command(&self, dir_entry: &DirEntry) {
-        match self.command {
-            None    => {},
-            Some(ref c) => { 
-                let template = liquid::parse(c, Default::default()).unwrap();
-                let mut context = self.setup_context(dir_entry);
-                let output = template.render(&mut context);
-                match output {
-                    Ok(res) => Command::new("sh").arg("-c").arg(res.unwrap()).spawn().expect("Failed to start command."),
-                    Err(e)    => {
-                        eprintln!("Command template error: {}", e);
-                        process::exit(1);
+    fn run_builtin(&mut self, dir_entry: &DirEntry) {
+        let path = dir_entry.path();
+        let is_dir = dir_entry.file_type().is_dir();
+        let outcome = match self.command {
+            Some(Action::Builtin(ref builtin)) => {
+                let destination = match builtin.destination() {
+                    Some(d) => {
+                        let mut context = match self.setup_context(dir_entry, d.variables()) {
+                            Ok(c)  => c,
+                            Err(e) => {
+                                self.executor.report_failure(&e);
+                                return;
+                            },
+                        };
+                        match action::render(&d.template, &mut context) {
+                            Ok(ref r) if r.is_empty() => Err(format!("The destination for {} is empty.", path.display())),
+                            Ok(r)                     => Ok(Some(PathBuf::from(r))),
+                            Err(e)                    => Err(e),
+                        }
                     },
+                    None    => Ok(None),
                 };
+                destination.map(|destination| {
+                    let description = builtin.describe(path, destination.as_deref());
+                    if self.executor.is_dry_run() {
+                        println!("{}", description);
+                        return None;
+                    }
+                    let result = builtin.run(path, is_dir, destination.as_deref());
+                    let is_deleted_dir = match *builtin {
+                        Builtin::Delete => is_dir,
+                        _               => false,
+                    };
+                    Some((result, description, is_deleted_dir))
+                })
             },
+            _ => return,
+        };
+        let (result, description, is_deleted_dir) = match outcome.unwrap_or_else(|e| self.abort(&e)) {
+            Some(o) => o,
+            None    => return,
+        };
+        if is_deleted_dir {
+            self.deleted_dirs.push(path.to_path_buf());
+        }
+        if let Err(e) = result {
+            self.executor.report_failure(&format!("Could not {}: {}", description, e));
+        }
+    }
+
+    /// Removes the directories matched by `delete`, deepest first.
+    fn delete_dirs(&mut self) {
+        while let Some(dir) = self.deleted_dirs.pop() {
+            if let Err(e) = builtin::delete_dir(&dir) {
+                self.executor.report_failure(&format!("Could not delete {}: {}", dir.display(), e));
+            }
+        }
+    }
+
+    fn add_to_batch(&mut self, dir_entry: &DirEntry) {
+        let embeds_names = match self.command {
+            Some(Action::Batch(ref c)) => c.embeds_names(),
+            _                          => false,
+        };
+        let name = dir_entry.path().as_os_str().to_os_string();
+        if embeds_names && name.to_str().is_none() {
+            self.executor.report_failure(&template_context::not_utf8(dir_entry.path()));
+            return;
+        }
+        // every name needs a pointer and a terminating NUL byte in the argument list,
+        // or a separator and quotes where `{{names}}` is shell-quoted
+        let name_length = template_filters::quote(&name.to_string_lossy()).len() + 1 + mem::size_of::<usize>();
+        if !self.batch.is_empty() && self.batch_length + name_length > self.max_batch_length {
+            self.run_batch();
+        }
+        self.batch_length += name_length;
+        self.batch.push(name);
+    }
+
+    fn run_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
         }
+        let built = match self.command {
+            Some(Action::Batch(ref c)) => {
+                let mut context = self.setup_batch_context();
+                c.build(&mut context, &self.batch)
+            },
+            _ => return,
+        };
+        let (command, description) = built.unwrap_or_else(|e| self.abort(&e));
+        self.batch.clear();
+        self.batch_length = 0;
+        self.executor.spawn(command, description);
     }
 
-    fn raw_walk(&self, dir: &String, max_depth: usize, color_config: &Option<ColorConfig>, color_mode: bool) {
+    fn raw_walk(&mut self, dir: &Path, max_depth: usize, color_config: &Option<ColorConfig>, color_mode: bool) {
         let dir_iter = WalkDir::new(dir).max_depth(max_depth).into_iter();
 
         'files: for entry in dir_iter {
+            if self.quit {
+                break;
+            }
             let entry = match entry{
                 Ok(e)  => e,
                 Err(e) => {
                     eprintln!("Error: {}", e);
+                    if let Some(ref mut summary) = self.summary {
+                        summary.error();
+                    }
                     continue 'files;
                 }
             };
-            if self.filters.test(&entry) != true {
+            if let Some(ref mut summary) = self.summary {
+                summary.visit(&entry);
+            }
+            if !self.filters.test(&entry, &self.search()) {
                     continue 'files;
             }
+            if let Some(ref mut summary) = self.summary {
+                summary.matched(&entry);
+            }
             self.print_attributes(&entry, color_config, color_mode);
             self.run_command(&entry);
         }
     }
 
-    fn process_entry(&self, entry: Result<DirEntry, walkdir::Error>, color_config: &Option<ColorConfig>, color_mode: bool){
+    fn process_entry(&mut self, entry: Result<DirEntry, walkdir::Error>, color_config: &Option<ColorConfig>, color_mode: bool){
         let entry = match entry{
             Ok(e)  => e,
             Err(e) => {
                 eprintln!("Error: {}", e);
+                if let Some(ref mut summary) = self.summary {
+                    summary.error();
+                }
                 return;
             }
         };
-        if self.filters.test(&entry) == true {
+        if let Some(ref mut summary) = self.summary {
+            summary.visit(&entry);
+        }
+        if self.filters.test(&entry, &self.search()) {
+            if let Some(ref mut summary) = self.summary {
+                summary.matched(&entry);
+            }
             self.print_attributes(&entry, color_config, color_mode);
             self.run_command(&entry);
         }
     }
 
-    fn dev_walk(&self, dir: &String, max_depth: usize, color_config: &Option<ColorConfig>, color_mode: bool, same_device: bool, ignore_hidden: bool){
+    fn dev_walk(&mut self, dir: &Path, max_depth: usize, color_config: &Option<ColorConfig>, color_mode: bool, same_device: bool, ignore_hidden: bool){
         let dir_iter = WalkDir::new(dir).max_depth(max_depth).into_iter();
 
-        let dev_id = match WalkDir::new(dir).into_iter().next() {
-            Some(e) => {
-                let dir_entry = e.expect("Failed to open directory entry.",);
-                dir_entry.metadata().map(|m| m.dev()).expect(&format!("Could not get device id from {:?}.", dir_entry)) 
-            }
+        let dev_id = match WalkDir::new(dir).into_iter().next().map(|e| e.map_err(|e| e.to_string())
+                                                                        .and_then(|e| e.metadata().map_err(|e| e.to_string()))) {
+            Some(Ok(metadata)) => metadata.dev(),
+            Some(Err(e))       => {
+                // a stale path of a path list must not end the whole search
+                eprintln!("Error: {}", e);
+                if let Some(ref mut summary) = self.summary {
+                    summary.error();
+                }
+                return;
+            },
             None => {
-                eprintln!("{} not found!", dir);
-                process::exit(1);
+                eprintln!("{} not found!", dir.display());
+                return;
             },
         };
 
+        // entries left out by the filters, for the summary
+        let skipped = Cell::new(0);
+        let keep = |keep: bool| {
+            if !keep {
+                skipped.set(skipped.get() + 1);
+            }
+            keep
+        };
 
         if same_device && ignore_hidden {
-            let filtered_iter = dir_iter.filter_entry(|e| e.metadata().map(|m| m.dev() == dev_id).unwrap_or(false))
-                                        .filter_entry(|e| !is_hidden(e)); 
+            let filtered_iter = dir_iter.filter_entry(|e| keep(e.metadata().map(|m| m.dev() == dev_id).unwrap_or(false)))
+                                        .filter_entry(|e| keep(!is_hidden(e))); 
             for entry in filtered_iter {
+                if self.quit {
+                    break;
+                }
                 self.process_entry(entry, color_config, color_mode);
             }
         } else if same_device {
-            let filtered_iter = dir_iter.filter_entry(|e| e.metadata().map(|m| m.dev() == dev_id).unwrap_or(false));
+            let filtered_iter = dir_iter.filter_entry(|e| keep(e.metadata().map(|m| m.dev() == dev_id).unwrap_or(false)));
             for entry in filtered_iter {
+                if self.quit {
+                    break;
+                }
                 self.process_entry(entry, color_config, color_mode);
             }
         } else if ignore_hidden {
-            let filtered_iter = dir_iter.filter_entry(|e| !is_hidden(e));
+            let filtered_iter = dir_iter.filter_entry(|e| keep(!is_hidden(e)));
             for entry in filtered_iter {
+                if self.quit {
+                    break;
+                }
                 self.process_entry(entry, color_config, color_mode);
             }
         } else {
             eprintln!("Implementation error: Use raw_walk instead!");
             process::exit(1);
         }
+        if let Some(ref mut summary) = self.summary {
+            summary.skipped(skipped.get());
+        }
     }
 
 
-    pub fn execute(&mut self, max_depth: usize, machine_mode: bool, ignore_hidden: bool, same_device: bool, color: bool, color_config: Option<ColorConfig>) {
-        let color_mode = if color {
-            stdout_is_tty()
+    /// Walks all search roots and returns the number of exec commands which failed.
+    pub fn execute(&mut self, max_depth: usize, machine_mode: bool, ignore_hidden: bool, same_device: bool, color: bool, color_config: Option<ColorConfig>) -> usize {
+        let is_tty = stdout_is_tty();
+        let color_mode = if color && self.format == Format::Plain && !self.print0 {
+            is_tty
         } else {
             false
         };
@@ -233,13 +703,63 @@ impl Query {
         if machine_mode {
             self.machine_mode = true
         }
+        self.commands.set_machine_mode(self.machine_mode);
+
+        self.hyperlinks = match self.hyperlink_mode {
+            HyperlinkMode::Always => true,
+            HyperlinkMode::Never  => false,
+            HyperlinkMode::Auto   => color_mode && hyperlink::terminal_supports(),
+        };
+
+        if is_tty && self.format == Format::Plain && !self.print0 && !self.machine_mode && !self.tree_mode && self.attributes.len() > 1 {
+            let width = terminal_size().map(|(w, _)| w as usize).unwrap_or(80);
+            self.table = Some(Table::new(&self.attributes, width));
+        }
 
-        for dir in &self.directories {
+        self.start_output();
+        for root in self.roots() {
+            if self.quit {
+                break;
+            }
+            let root = match root {
+                Ok(r)  => r,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    self.root_errors += 1;
+                    continue;
+                },
+            };
+            let dir = root.path;
+            if !root.listed {
+                if let Err(e) = fs::symlink_metadata(&dir) {
+                    eprintln!("Error: {}: {}", dir.display(), e);
+                    self.root_errors += 1;
+                    continue;
+                }
+            }
+            // listed paths are single entries, they are not walked again
+            // and their root is the working directory
+            let max_depth = if root.listed { 0 } else { max_depth };
+            self.current_root = if root.listed { PathBuf::from(".") } else { dir.clone() };
+            if self.tree_mode {
+                self.tree = Some(Tree::new(&dir));
+            }
             if same_device || ignore_hidden {
-                self.dev_walk(dir, max_depth, &color_config, color_mode, same_device, ignore_hidden);
+                self.dev_walk(&dir, max_depth, &color_config, color_mode, same_device, ignore_hidden);
             } else {
-                self.raw_walk(dir, max_depth, &color_config, color_mode);
+                self.raw_walk(&dir, max_depth, &color_config, color_mode);
             }
+            if let Some(tree) = self.tree.take() {
+                tree.print(&color_config, color_mode, self.hyperlinks, self.machine_mode);
+            }
+        }
+        self.finish_output();
+        self.run_batch();
+        self.delete_dirs();
+        let failed = self.executor.finish();
+        if let Some(ref summary) = self.summary {
+            summary.print(self.machine_mode);
         }
+        failed
     }
 }
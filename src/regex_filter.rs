@@ -1,9 +1,11 @@
 use filter;
 use filter::Filter;
+use filter::Search;
 use filter_tree::FilterTuple;
 use walkdir::DirEntry;
 use regex::Regex;
 use std::process;
+use std::borrow::Cow;
 
 pub struct RegexFilter {
     attribute : filter::Attribute,
@@ -20,17 +22,17 @@ impl RegexFilter {
                 process::exit(1);
             },
         };
-        let flip = match ft.operator {
-            filter::CompOp::Unlike => true,
-            _ => false,
-        };
-        RegexFilter{regex: re, attribute: ft.attribute.clone(), flip: flip}
+        let flip = ft.operator == filter::CompOp::Unlike;
+        RegexFilter{regex: re, attribute: ft.attribute.clone(), flip}
     }
 
-    fn get_attribute<'a>(&self, direntry: &'a DirEntry) -> Option<&'a str> {
+    fn get_attribute<'a>(&self, direntry: &'a DirEntry, search: &Search) -> Option<Cow<'a, str>> {
         match self.attribute {
-            filter::Attribute::Name => direntry.path().to_str(),
-            filter::Attribute::Basename => direntry.file_name().to_str(),
+            filter::Attribute::Name => direntry.path().to_str().map(Cow::Borrowed),
+            filter::Attribute::Basename => direntry.file_name().to_str().map(Cow::Borrowed),
+            filter::Attribute::Abspath | filter::Attribute::Relpath | filter::Attribute::Realpath => {
+                self.attribute.derived_path(direntry, search.root).and_then(|p| p.to_str().map(|s| Cow::Owned(String::from(s))))
+            },
             _ => {
                 eprintln!("Operator ~ not supported for attribute {:?}", self.attribute);
                 process::exit(1);
@@ -40,12 +42,13 @@ impl RegexFilter {
 }
 
 impl Filter for RegexFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
-        let attr = self.get_attribute(dir_entry);
+    fn test(&self, dir_entry: &DirEntry, search: &Search) -> bool {
+        let attr = self.get_attribute(dir_entry, search);
         if attr.is_none() {
             eprintln!("UTF-8 Error");
             return false;
         }
-        if !self.flip {self.regex.is_match(attr.unwrap())} else {!self.regex.is_match(attr.unwrap())}
+        let attr = attr.unwrap();
+        if !self.flip {self.regex.is_match(&attr)} else {!self.regex.is_match(&attr)}
     }
 }
@@ -0,0 +1,249 @@
+//! Search roots
+//!
+//! Turns the directories of the from clause into the paths which are actually walked.
+//! A directory given as `-` is replaced by a list of paths read from stdin. Each
+//! listed path is evaluated as a single entry, the disk is not walked again.
+//! Like in a shell, unquoted directories get a leading `~` and `$VAR`/`${VAR}` expanded,
+//! double quoted ones only their variables and single quoted ones neither. Unlike in a
+//! shell, all of them are then matched as glob patterns, so a single directory can
+//! expand into several roots, and the quotes only keep spaces, commas and semicolons
+//! in the directory.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::process;
+use glob;
+
+/// A directory of the from clause.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Directory {
+    Plain(String),
+    SingleQuoted(String),
+    DoubleQuoted(String),
+}
+
+/// A path which is searched.
+pub struct Root {
+    pub path: PathBuf,
+    /// The path was read from a path list and is evaluated without walking it.
+    pub listed: bool,
+}
+
+impl Root {
+    fn walked(path: PathBuf) -> Root {
+        Root{path, listed: false}
+    }
+
+    fn listed(path: PathBuf) -> Root {
+        Root{path, listed: true}
+    }
+}
+
+/// Reads a list of paths, e.g. the output of `git ls-files -z` or `locate`.
+/// If the input contains a NUL byte, the paths are expected to be NUL-separated,
+/// otherwise they are separated by newlines. Empty entries are skipped.
+pub fn read_path_list<R: Read>(mut reader: R) -> io::Result<Vec<PathBuf>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+    let separator = if buffer.contains(&0u8) { 0u8 } else { b'\n' };
+    Ok(buffer.split(|b| *b == separator)
+             .filter(|p| !p.is_empty())
+             .map(|p| PathBuf::from(OsStr::from_bytes(p)))
+             .collect())
+}
+
+/// Reads the path list from the given file, or from stdin if the file is `-`.
+pub fn load_path_list(file: &str) -> Vec<Root> {
+    let paths = if file == "-" {
+        let stdin = io::stdin();
+        let locked = stdin.lock();
+        read_path_list(locked)
+    } else {
+        match File::open(file) {
+            Ok(f)  => read_path_list(f),
+            Err(e) => {
+                eprintln!("Could not open path list {}: {}", file, e);
+                process::exit(1);
+            },
+        }
+    };
+    match paths {
+        Ok(p)  => p.into_iter().map(Root::listed).collect(),
+        Err(e) => {
+            eprintln!("Could not read path list {}: {}", file, e);
+            process::exit(1);
+        },
+    }
+}
+
+fn expand_home(dir: &str) -> Result<String, String> {
+    if dir != "~" && !dir.starts_with("~/") {
+        return Ok(String::from(dir));
+    }
+    match env::home_dir() {
+        Some(home) => Ok(format!("{}{}", home.display(), &dir[1..])),
+        None       => Err(format!("Could not expand {}: home directory unknown.", dir)),
+    }
+}
+
+fn expand_variables(dir: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = dir;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, tail) = if after.starts_with('{') {
+            match after.find('}') {
+                Some(end) => (&after[1..end], &after[end + 1..]),
+                None      => return Err(format!("Missing '}}' in directory {}", dir)),
+            }
+        } else {
+            let end = after.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            match env::var(name) {
+                Ok(value) => expanded.push_str(&value),
+                Err(_)    => return Err(format!("Environment variable {} in directory {} is not set.", name, dir)),
+            }
+        }
+        rest = tail;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn is_pattern(dir: &str) -> bool {
+    dir.contains(['*', '?', '['])
+}
+
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob::glob(pattern).map_err(|e| format!("Invalid glob pattern {}: {}", pattern, e))?;
+    let mut roots = Vec::new();
+    for path in paths {
+        match path {
+            Ok(p)  => roots.push(p),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    if roots.is_empty() {
+        return Err(format!("No directory matches {}", pattern));
+    }
+    Ok(roots)
+}
+
+/// Matches a directory as glob pattern. A pattern which names an existing path is taken literally.
+fn expand_pattern(dir: String) -> Result<Vec<PathBuf>, String> {
+    if is_pattern(&dir) && fs::symlink_metadata(&dir).is_err() {
+        expand_glob(&dir)
+    } else {
+        Ok(vec![PathBuf::from(dir)])
+    }
+}
+
+fn expand_plain(dir: &str) -> Result<Vec<PathBuf>, String> {
+    expand_pattern(expand_variables(&expand_home(dir)?)?)
+}
+
+/// Expands the directories of a query into the search roots. A directory which
+/// cannot be expanded or matches nothing is an error, the others are still searched.
+pub fn expand(directories: &[Directory]) -> Vec<Result<Root, String>> {
+    let mut roots = Vec::new();
+    for dir in directories {
+        let expanded = match *dir {
+            Directory::Plain(ref d) if d == "-" => {
+                roots.extend(load_path_list(d).into_iter().map(Ok));
+                continue;
+            },
+            Directory::Plain(ref d)        => expand_plain(d),
+            Directory::DoubleQuoted(ref d) => expand_variables(d).and_then(expand_pattern),
+            Directory::SingleQuoted(ref d) => expand_pattern(d.clone()),
+        };
+        match expanded {
+            Ok(paths) => roots.extend(paths.into_iter().map(|p| Ok(Root::walked(p)))),
+            Err(e)    => roots.push(Err(e)),
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, expand_home, expand_plain, expand_variables, is_pattern, read_path_list, Directory};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn walked(directories: &[Directory]) -> Vec<Result<PathBuf, String>> {
+        expand(directories).into_iter().map(|r| r.map(|root| { assert!(!root.listed); root.path })).collect()
+    }
+
+    #[test]
+    fn reads_path_lists() {
+        let list = |input: &[u8]| read_path_list(input).unwrap();
+        assert_eq!(list(b"a\nb c\n\nd\n"), vec![PathBuf::from("a"), PathBuf::from("b c"), PathBuf::from("d")]);
+        assert_eq!(list(b"a\nb\0c\0"), vec![PathBuf::from("a\nb"), PathBuf::from("c")]);
+        assert!(list(b"").is_empty());
+    }
+
+    #[test]
+    fn expands_variables() {
+        env::set_var("FRUST_TEST_SHARE", "NAS Share");
+        assert_eq!(expand_variables("/mnt/$FRUST_TEST_SHARE/x"), Ok(String::from("/mnt/NAS Share/x")));
+        assert_eq!(expand_variables("/mnt/${FRUST_TEST_SHARE}_old"), Ok(String::from("/mnt/NAS Share_old")));
+        assert_eq!(expand_variables("/a$/b$"), Ok(String::from("/a$/b$")));
+        assert!(expand_variables("/mnt/${FRUST_TEST_SHARE").is_err());
+        assert!(expand_variables("/mnt/$FRUST_TEST_UNSET").is_err());
+    }
+
+    #[test]
+    fn expands_home() {
+        if let Some(home) = env::home_dir() {
+            assert_eq!(expand_home("~/x"), Ok(format!("{}/x", home.display())));
+            assert_eq!(expand_home("~"), Ok(format!("{}", home.display())));
+        }
+        assert_eq!(expand_home("~user/x"), Ok(String::from("~user/x")));
+        assert_eq!(expand_home("/a/~"), Ok(String::from("/a/~")));
+    }
+
+    #[test]
+    fn recognizes_patterns() {
+        assert!(is_pattern("/home/*/Downloads"));
+        assert!(is_pattern("file?.txt"));
+        assert!(is_pattern("[ab]"));
+        assert!(!is_pattern("/home/user/My Documents"));
+    }
+
+    #[test]
+    fn expands_quoted_and_unquoted_globs() {
+        let dir = env::temp_dir().join(format!("frust-roots-{}", process::id()));
+        for share in &["NAS Share", "Other Share", "[draft]"] {
+            fs::create_dir_all(dir.join(share).join("Downloads")).unwrap();
+        }
+        env::set_var("FRUST_TEST_ROOT", &dir);
+        let downloads = |share: &str| Ok(dir.join(share).join("Downloads"));
+        let pattern = format!("{}/* Share/Downloads", dir.display());
+        let matches = vec![downloads("NAS Share"), downloads("Other Share")];
+        assert_eq!(walked(&[Directory::SingleQuoted(pattern.clone())]), matches);
+        assert_eq!(walked(&[Directory::DoubleQuoted(String::from("$FRUST_TEST_ROOT/* Share/Downloads"))]), matches);
+        assert_eq!(expand_plain("$FRUST_TEST_ROOT/Other*/Downloads"), Ok(vec![dir.join("Other Share/Downloads")]));
+        // variables are only expanded outside of single quotes
+        assert!(walked(&[Directory::SingleQuoted(String::from("$FRUST_TEST_ROOT/*"))])[0].is_err());
+        // an existing path is taken literally, even if it looks like a pattern
+        assert_eq!(walked(&[Directory::SingleQuoted(format!("{}/[draft]", dir.display()))]), vec![Ok(dir.join("[draft]"))]);
+        assert_eq!(expand_plain(&format!("{}/[[]draft]/*", dir.display())), Ok(vec![dir.join("[draft]/Downloads")]));
+        assert!(expand_plain(&format!("{}/*/Uploads", dir.display())).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use filter;
 use filter::Filter;
+use filter::Search;
 use walkdir::DirEntry;
 use nom::IResult;
 use nom::Needed;
@@ -88,7 +89,7 @@ impl SizeFilter {
 }
 
 impl Filter for SizeFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         match self.comp_op {
             filter::CompOp::Lower        => dir_entry.metadata().unwrap().len() < self.size,
             filter::CompOp::LowerEqual   => dir_entry.metadata().unwrap().len() <= self.size,
@@ -0,0 +1,80 @@
+//! Counters for `--summary`
+//!
+//! The walk counts the visited entries, errors and matches. After the last search
+//! root the summary is printed to stderr, so it does not mix with the output.
+
+use formatter;
+use walkdir::DirEntry;
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::time::Instant;
+
+pub struct Summary {
+    start: Instant,
+    /// Matched entries per file type.
+    matched: BTreeMap<String, usize>,
+    apparent_size: u64,
+    allocated_size: u64,
+    directories: usize,
+    entries: usize,
+    /// Entries which were left out by `--ignore-hidden` or `--same-device`.
+    skipped: usize,
+    errors: usize,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary{start: Instant::now(), matched: BTreeMap::new(), apparent_size: 0, allocated_size: 0,
+                directories: 0, entries: 0, skipped: 0, errors: 0}
+    }
+
+    /// Counts an entry which was visited by the walk.
+    pub fn visit(&mut self, dir_entry: &DirEntry) {
+        self.entries += 1;
+        if dir_entry.file_type().is_dir() {
+            self.directories += 1;
+        }
+    }
+
+    /// Counts an entry which passed the filter expression.
+    pub fn matched(&mut self, dir_entry: &DirEntry) {
+        let file_type = formatter::format_filetype(dir_entry.file_type(), true);
+        *self.matched.entry(file_type).or_insert(0) += 1;
+        if let Ok(metadata) = dir_entry.metadata() {
+            self.apparent_size += metadata.len();
+            // st_blocks is always counted in units of 512 bytes
+            self.allocated_size += metadata.blocks() * 512;
+        }
+    }
+
+    pub fn skipped(&mut self, count: usize) {
+        self.skipped += count;
+    }
+
+    pub fn error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn print(&self, machine_mode: bool) {
+        let matched: usize = self.matched.values().sum();
+        let by_type: Vec<String> = self.matched.iter().map(|(t, c)| format!("{} {}", c, t)).collect();
+        if by_type.is_empty() {
+            eprintln!("Matched 0 entries");
+        } else {
+            eprintln!("Matched {} entries: {}", matched, by_type.join(", "));
+        }
+        eprintln!("Total size {} apparent, {} allocated",
+                  formatter::format_filesize(self.apparent_size, machine_mode),
+                  formatter::format_filesize(self.allocated_size, machine_mode));
+        eprintln!("Scanned {} entries in {} directories, {} skipped, {} errors",
+                  self.entries, self.directories, self.skipped, self.errors);
+        let elapsed = self.start.elapsed();
+        eprintln!("Elapsed {}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
+    }
+}
@@ -0,0 +1,198 @@
+//! Aligned table output for terminals
+//!
+//! If several attributes are selected and stdout is a terminal, the plain output is
+//! printed as a table with a header. Sizes and ids are right-aligned, times have a
+//! fixed width and paths share the rest of the terminal width. Paths which are too
+//! long lose their beginning, so that the file name stays visible. Escape sequences
+//! for colors and links do not count towards the width.
+
+use filter::{Attribute, Column};
+use unicode_width::UnicodeWidthChar;
+
+const SEPARATOR: &str = "  ";
+const ELLIPSIS: char = '…';
+const MIN_PATH_WIDTH: usize = 10;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+struct Layout {
+    width: usize,
+    align: Align,
+    /// Paths are shortened at their start, everything else at its end.
+    is_path: bool,
+}
+
+pub struct Table {
+    layouts: Vec<Layout>,
+}
+
+impl Table {
+    /// Distributes the terminal width over the columns.
+    pub fn new(columns: &[Column], terminal_width: usize) -> Table {
+        let mut layouts: Vec<Layout> = columns.iter().map(|c| {
+            let (width, align, is_path) = match c.attribute {
+                Attribute::Name | Attribute::Basename | Attribute::Abspath
+                | Attribute::Relpath | Attribute::Realpath => (0, Align::Left, true),
+                Attribute::Size                       => (10, Align::Right, false),
+                Attribute::Mtime | Attribute::Ctime | Attribute::Atime | Attribute::Btime => (19, Align::Left, false),
+                Attribute::Filetype                   => (12, Align::Left, false),
+                Attribute::Mimetype                   => (24, Align::Left, false),
+                Attribute::Inode                      => (10, Align::Right, false),
+                Attribute::Uid | Attribute::Gid       => (6, Align::Right, false),
+                Attribute::Command(_)                 => (20, Align::Left, false),
+            };
+            // the header must fit too
+            Layout{width: if is_path { 0 } else { width.max(display_width(&c.name())) }, align, is_path}
+        }).collect();
+
+        let paths = layouts.iter().filter(|l| l.is_path).count();
+        let used: usize = layouts.iter().map(|l| l.width).sum::<usize>() + SEPARATOR.len() * layouts.len().saturating_sub(1);
+        if let Some(share) = terminal_width.saturating_sub(used).checked_div(paths) {
+            let path_width = share.max(MIN_PATH_WIDTH);
+            for layout in layouts.iter_mut().filter(|l| l.is_path) {
+                layout.width = path_width;
+            }
+        }
+        Table{layouts}
+    }
+
+    /// Formats one line of the table, the cells may contain color codes.
+    pub fn row(&self, cells: &[String]) -> String {
+        let last = cells.len().saturating_sub(1);
+        let mut line = String::new();
+        for (i, (cell, layout)) in cells.iter().zip(self.layouts.iter()).enumerate() {
+            if i > 0 {
+                line.push_str(SEPARATOR);
+            }
+            // control characters like newlines in names would break the lines
+            let cell: String = cell.chars().map(|c| if c.is_control() && c != '\x1b' { '?' } else { c }).collect();
+            let cell = if display_width(&cell) <= layout.width {
+                cell
+            } else if layout.is_path {
+                truncate_start(&cell, layout.width)
+            } else {
+                truncate_end(&cell, layout.width)
+            };
+            let padding = " ".repeat(layout.width.saturating_sub(display_width(&cell)));
+            match layout.align {
+                Align::Right               => { line.push_str(&padding); line.push_str(&cell); },
+                Align::Left if i == last   => line.push_str(&cell),
+                Align::Left                => { line.push_str(&cell); line.push_str(&padding); },
+            }
+        }
+        line
+    }
+}
+
+/// Splits a string into color escape sequences and visible characters.
+fn pieces(s: &str) -> Vec<(&str, usize)> {
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let length = if let Some(sequence) = rest.strip_prefix("\x1b[") {
+            sequence.find(|c: char| ('@'..='~').contains(&c)).map(|e| e + 3).unwrap_or(rest.len())
+        } else if rest.starts_with("\x1b]") {
+            // operating system commands like hyperlinks end with ESC \ or BEL
+            match (rest.find("\x1b\\"), rest.find('\x07')) {
+                (Some(e), _) => e + 2,
+                (None, Some(b)) => b + 1,
+                (None, None) => rest.len(),
+            }
+        } else {
+            c.len_utf8()
+        };
+        let width = if c == '\x1b' { 0 } else { c.width().unwrap_or(0) };
+        pieces.push((&rest[..length], width));
+        rest = &rest[length..];
+    }
+    pieces
+}
+
+/// Number of terminal columns a string occupies, ignoring color codes.
+fn display_width(s: &str) -> usize {
+    pieces(s).iter().map(|&(_, w)| w).sum()
+}
+
+/// Drops visible characters from the start, keeping all color codes.
+fn truncate_start(s: &str, width: usize) -> String {
+    let pieces = pieces(s);
+    let mut excess = display_width(s) + 1 - width.min(display_width(s) + 1);
+    let mut truncated = String::new();
+    let mut ellipsis = false;
+    for &(piece, w) in &pieces {
+        if w > 0 && excess > 0 {
+            excess = excess.saturating_sub(w);
+            continue;
+        }
+        if w > 0 && !ellipsis {
+            truncated.push(ELLIPSIS);
+            ellipsis = true;
+        }
+        truncated.push_str(piece);
+    }
+    truncated
+}
+
+/// Drops visible characters from the end, keeping all color codes.
+fn truncate_end(s: &str, width: usize) -> String {
+    let mut remaining = width.saturating_sub(1);
+    let mut truncated = String::new();
+    let mut ellipsis = false;
+    for (piece, w) in pieces(s) {
+        if w > remaining {
+            if !ellipsis {
+                truncated.push(ELLIPSIS);
+                ellipsis = true;
+            }
+            remaining = 0;
+            if w > 0 {
+                continue;
+            }
+        } else {
+            remaining -= w;
+        }
+        truncated.push_str(piece);
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_width, truncate_end, truncate_start};
+
+    #[test]
+    fn measures_without_escape_sequences() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("\x1b[01;34mabc\x1b[0m"), 3);
+        assert_eq!(display_width("日本"), 4);
+    }
+
+    #[test]
+    fn truncates_the_start_of_paths() {
+        assert_eq!(truncate_start("abcdef", 4), "…def");
+        assert_eq!(truncate_start("\x1b[34mabcdef\x1b[0m", 4), "\x1b[34m…def\x1b[0m");
+        assert_eq!(truncate_start("日本語", 4), "…語");
+    }
+
+    #[test]
+    fn truncates_the_end_of_other_columns() {
+        assert_eq!(truncate_end("abcdef", 4), "abc…");
+        assert_eq!(truncate_end("\x1b[34mabcdef\x1b[0m", 4), "\x1b[34mabc…\x1b[0m");
+        assert_eq!(truncate_end("日本語", 4), "日…");
+        assert_eq!(display_width(&truncate_end("日本語", 4)), 3);
+    }
+
+    #[test]
+    fn keeps_hyperlinks_whole() {
+        let link = "\x1b]8;;file:///tmp/abcdef\x1b\\abcdef\x1b]8;;\x1b\\";
+        assert_eq!(display_width(link), 6);
+        assert_eq!(truncate_start(link, 4), "\x1b]8;;file:///tmp/abcdef\x1b\\…def\x1b]8;;\x1b\\");
+        assert_eq!(display_width("\x1b]8;;file:///x\x07x\x1b]8;;\x07"), 1);
+    }
+}
@@ -0,0 +1,88 @@
+//! Variables of the templates
+//!
+//! Only the variables a template references are computed, so a template using
+//! `{{name}}` alone does not stat the file.
+//!
+//! The values are strings, so an entry whose path is not valid UTF-8 cannot be
+//! put into a template which references one of the path variables. Setting up
+//! the context fails for it instead of substituting something else.
+
+use formatter;
+use paths;
+use timestamps;
+use mime_guess;
+use template_filters;
+use liquid::{Context, Value};
+use walkdir::DirEntry;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::os::unix::fs::MetadataExt;
+
+/// Template variables which need the metadata of an entry.
+const METADATA_VARIABLES: [&str; 10] = ["size", "mtime", "ctime", "atime", "btime", "type", "uid", "gid", "user", "group"];
+
+/// The error for an entry whose path cannot be put into a template.
+pub fn not_utf8(path: &Path) -> String {
+    format!("Skipping {}: its path is not valid UTF-8 and cannot be used in a template.", path.display())
+}
+
+/// Converts a path variable, failing for a path which is not valid UTF-8.
+fn text(value: &OsStr, dir_entry: &DirEntry) -> Result<String, String> {
+    value.to_str()
+         .map(String::from)
+         .ok_or_else(|| not_utf8(dir_entry.path()))
+}
+
+/// Returns the value of a template variable, or None if there is no such variable.
+fn context_value(variable: &str, dir_entry: &DirEntry, metadata: &Option<fs::Metadata>, root: &Path, machine_mode: bool) -> Result<Option<String>, String> {
+    let not_available = String::from("N/A");
+    let value = match variable {
+        "name"     => text(dir_entry.path().as_os_str(), dir_entry)?,
+        "basename" => text(dir_entry.file_name(), dir_entry)?,
+        "dirname"  => match dir_entry.path().parent() {
+            Some(p) if !p.as_os_str().is_empty() => text(p.as_os_str(), dir_entry)?,
+            _                                    => String::from("."),
+        },
+        "stem"     => text(dir_entry.path().file_stem().unwrap_or_default(), dir_entry)?,
+        "extension" => text(dir_entry.path().extension().unwrap_or_default(), dir_entry)?,
+        "root"     => text(root.as_os_str(), dir_entry)?,
+        "relpath"  => text(paths::root_relative(dir_entry.path(), root).as_os_str(), dir_entry)?,
+        "abspath"  => text(paths::absolute(dir_entry.path()).as_os_str(), dir_entry)?,
+        "realpath" => text(paths::canonical(dir_entry.path()).as_os_str(), dir_entry)?,
+        "depth"    => format!("{}", dir_entry.depth()),
+        "mimetype" => formatter::format_mimetype(mime_guess::guess_mime_type(dir_entry.path()), machine_mode),
+        "inode"    => format!("{}", dir_entry.ino()),
+        v if METADATA_VARIABLES.contains(&v) => match *metadata {
+            None        => not_available,
+            Some(ref m) => match variable {
+                "size"  => formatter::format_filesize(m.len(), machine_mode),
+                "mtime" => m.modified().map(|t| formatter::format_systime(t, machine_mode)).unwrap_or(not_available),
+                "ctime" => formatter::format_systime(timestamps::ctime(m), machine_mode),
+                "btime" => m.created().map(|t| formatter::format_systime(t, machine_mode)).unwrap_or(not_available),
+                "atime" => m.accessed().map(|t| formatter::format_systime(t, machine_mode)).unwrap_or(not_available),
+                "uid"   => format!("{}", m.uid()),
+                "gid"   => format!("{}", m.gid()),
+                "user"  => formatter::format_user(m.uid()),
+                "group" => formatter::format_group(m.gid()),
+                _       => formatter::format_filetype(m.file_type(), machine_mode),
+            },
+        },
+        _          => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// Sets up a context with the variables referenced by a template.
+pub fn setup_context(dir_entry: &DirEntry, variables: &[String], root: &Path, machine_mode: bool) -> Result<Context, String> {
+    let mut context = Context::new();
+    template_filters::register(&mut context);
+    let needs_metadata = variables.iter().any(|v| METADATA_VARIABLES.contains(&v.as_str()));
+    let metadata = if needs_metadata { dir_entry.metadata().ok() } else { None };
+    for variable in variables {
+        if let Some(value) = context_value(variable, dir_entry, &metadata, root, machine_mode)? {
+            context.set_val(variable, Value::Str(value));
+        }
+    }
+    Ok(context)
+}
@@ -0,0 +1,173 @@
+//! Custom liquid filters for the templates of the exec clause
+//!
+//! `date` replaces the filter of liquid, which does not understand the times of frust.
+
+use liquid::{Context, Value, FilterError};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use formatter;
+use timestamps;
+use std::path::{Component, Path, PathBuf};
+
+/// Filters which are built into liquid.
+const BUILTIN_FILTERS: [&str; 30] = ["append", "capitalize", "ceil", "date", "divided_by", "downcase", "escape",
+    "escape_once", "first", "floor", "join", "last", "minus", "modulo", "pluralize", "plus", "prepend", "remove",
+    "remove_first", "replace", "replace_first", "round", "size", "slice", "sort", "split", "strip_html", "times",
+    "truncatewords", "upcase"];
+
+/// Filters which are added by `register`.
+const CUSTOM_FILTERS: [&str; 6] = ["shell_quote", "relative_to", "with_extension", "date", "human_size", "human"];
+
+fn string_argument(args: &[Value], filter: &str) -> Result<String, FilterError> {
+    match args.first() {
+        Some(Value::Str(s)) => Ok(s.clone()),
+        Some(v)                  => Ok(v.to_string()),
+        None                     => Err(FilterError::InvalidArgumentCount(format!("{} expects one argument", filter))),
+    }
+}
+
+/// Lexically computes `path` relative to `base`, e.g. `{{name | relative_to: root}}`.
+pub fn relative_to(input: &Value, args: &[Value]) -> Result<Value, FilterError> {
+    let base = string_argument(args, "relative_to")?;
+    Ok(Value::Str(relative_path(Path::new(&input.to_string()), Path::new(&base)).to_string_lossy().into_owned()))
+}
+
+/// Lexically computes `path` relative to `base`.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_components: Vec<Component> = path.components().filter(|c| *c != Component::CurDir).collect();
+    let base_components: Vec<Component> = base.components().filter(|c| *c != Component::CurDir).collect();
+    let common = path_components.iter().zip(base_components.iter()).take_while(|&(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component.as_os_str());
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// Replaces the extension of a path, e.g. `{{name | with_extension: 'bak'}}`.
+pub fn with_extension(input: &Value, args: &[Value]) -> Result<Value, FilterError> {
+    let extension = string_argument(args, "with_extension")?;
+    Ok(Value::Str(Path::new(&input.to_string()).with_extension(extension).to_string_lossy().into_owned()))
+}
+
+/// Converts seconds since the epoch to the local time. chrono panics if the offset
+/// of the time zone moves a time beyond its range, so the first and last day are refused.
+fn local_time(seconds: i64, nanoseconds: u32) -> Option<DateTime<Local>> {
+    let utc = NaiveDateTime::from_timestamp_opt(seconds, nanoseconds)?;
+    utc.date().pred_opt()?.succ_opt()?.succ_opt()?;
+    Local.timestamp_opt(seconds, nanoseconds).single()
+}
+
+/// Formats a time with a strftime format, e.g. `{{mtime | date: '%Y%m%d'}}`.
+/// The time may be given in seconds since the epoch or as `YYYY-MM-DD HH:MM:SS`.
+pub fn date(input: &Value, args: &[Value]) -> Result<Value, FilterError> {
+    let format = string_argument(args, "date")?;
+    let text = input.to_string();
+    let time = match (timestamps::parse_epoch(&text), text.parse::<f64>()) {
+        (Some(d), _) if d.as_secs() > i64::MAX as u64 => None,
+        (Some(d), _)        => local_time(d.as_secs() as i64, d.subsec_nanos()),
+        (None, Ok(seconds)) => local_time(seconds.floor() as i64, 0),
+        (None, Err(_))      => Local.datetime_from_str(&text, "%Y-%m-%d %H:%M:%S").ok(),
+    };
+    match time {
+        Some(t) => Ok(Value::Str(format!("{}", t.format(&format)))),
+        None    => Err(FilterError::InvalidType(format!("{} is not a time", text))),
+    }
+}
+
+/// Formats a number of bytes for humans, e.g. `{{size | human_size}}` in machine readable mode.
+pub fn human_size(input: &Value, _args: &[Value]) -> Result<Value, FilterError> {
+    let bytes = match *input {
+        Value::Num(n)     => n as u64,
+        ref v             => match v.to_string().parse::<u64>() {
+            Ok(b)  => b,
+            Err(_) => return Ok(v.clone()),
+        },
+    };
+    Ok(Value::Str(formatter::format_filesize(bytes, false)))
+}
+
+/// Quotes a string for the use in a shell script, arrays are quoted element-wise
+/// and joined with spaces, e.g. `exec sh 'mv {{name | shell_quote}} /tmp'`.
+pub fn shell_quote(input: &Value, _args: &[Value]) -> Result<Value, FilterError> {
+    match *input {
+        Value::Str(ref s)   => Ok(Value::Str(quote(s))),
+        Value::Array(ref a) => Ok(Value::Str(a.iter().map(|v| quote(&v.to_string())).collect::<Vec<String>>().join(" "))),
+        ref v               => Ok(Value::Str(quote(&v.to_string()))),
+    }
+}
+
+/// Quotes a string for a shell with single quotes.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace("'", "'\\''"))
+}
+
+/// Quotes a word for a shell only if it contains characters other than
+/// alphanumerics and `-_./,:=+@%`, so that it can be displayed unambiguously.
+pub fn quote_if_needed(s: &str) -> String {
+    let is_safe = |c: char| c.is_alphanumeric() || "-_./,:=+@%".contains(c);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        String::from(s)
+    } else {
+        quote(s)
+    }
+}
+
+/// Returns true if a filter with this name is available in the templates.
+pub fn is_known(name: &str) -> bool {
+    BUILTIN_FILTERS.contains(&name) || CUSTOM_FILTERS.contains(&name)
+}
+
+/// Adds all custom filters to a context.
+pub fn register(context: &mut Context) {
+    context.add_filter("shell_quote", Box::new(shell_quote));
+    context.add_filter("relative_to", Box::new(relative_to));
+    context.add_filter("with_extension", Box::new(with_extension));
+    context.add_filter("date", Box::new(date));
+    context.add_filter("human_size", Box::new(human_size));
+    context.add_filter("human", Box::new(human_size));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote, quote_if_needed, shell_quote};
+    use liquid::Value;
+    use std::process::Command;
+
+    /// Lets a shell parse the quoted words back.
+    fn shell_words(quoted: &str) -> String {
+        let output = Command::new("sh").arg("-c").arg(format!("printf '<%s>' {}", quoted)).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn quotes_for_the_shell() {
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        for s in &["a b", "it's", "$(touch x)", "`id`", "\"\\", "", "*"] {
+            assert_eq!(shell_words(&quote(s)), format!("<{}>", s));
+        }
+    }
+
+    #[test]
+    fn quotes_only_if_needed() {
+        assert_eq!(quote_if_needed("./src/main.rs"), "./src/main.rs");
+        assert_eq!(quote_if_needed("a=b,c@d%e+f:g"), "a=b,c@d%e+f:g");
+        assert_eq!(quote_if_needed(""), "''");
+        assert_eq!(quote_if_needed("a b"), "'a b'");
+        assert_eq!(quote_if_needed("a;b"), "'a;b'");
+    }
+
+    #[test]
+    fn shell_quote_filter_quotes_every_name() {
+        let names = Value::Array(vec![Value::Str(String::from("a b")), Value::Str(String::from("it's"))]);
+        let quoted = shell_quote(&names, &[]).unwrap().to_string();
+        assert_eq!(shell_words(&quoted), "<a b><it's>");
+        assert_eq!(shell_quote(&Value::Str(String::from("$HOME")), &[]).unwrap().to_string(), "'$HOME'");
+    }
+}
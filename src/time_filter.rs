@@ -1,5 +1,7 @@
 use filter;
 use filter::Filter;
+use filter::Search;
+use timestamps;
 use walkdir::DirEntry;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use nom::IResult;
@@ -19,7 +21,7 @@ enum RelativeTimeUnit {
 }
 
 struct RelativeTimeTuple {
-    dist: i64,
+    dist: f64,
     unit: RelativeTimeUnit,
 }
 
@@ -38,13 +40,13 @@ named!(reltime_unit<RelativeTimeUnit>,
        )
 );
 
-named!(integer_number<i64>,
+named!(decimal_number<f64>,
        do_parse!( 
-           int: re_bytes_find!("-?[0-9]+") >>
-           (match String::from_utf8_lossy(int).into_owned().parse::<i64>() {
-               Ok(i) => i,
+           number: re_bytes_find!("^-?[0-9]+(\\.[0-9]+)?") >>
+           (match String::from_utf8_lossy(number).into_owned().parse::<f64>() {
+               Ok(n) => n,
                Err(e) => {
-                   eprintln!("Could not parse to integer: {}", e);
+                   eprintln!("Could not parse to number: {}", e);
                    process::exit(1);
                },
             }
@@ -54,7 +56,7 @@ named!(integer_number<i64>,
 
 named!(reltime_parameter<RelativeTimeTuple>, 
        do_parse!(
-           i: integer_number >> 
+           i: decimal_number >> 
            u: reltime_unit >>
            (RelativeTimeTuple{dist: i, unit: u})
        )
@@ -62,11 +64,11 @@ named!(reltime_parameter<RelativeTimeTuple>,
 
 named!(onlydate<chrono::DateTime<Local>>, 
        do_parse!(
-           year: re_bytes_find!("[0-9]{4}") >>
+           year: re_bytes_find!("^[0-9]{4}") >>
            tag!("-") >>
-           month: re_bytes_find!("[0-9]{2}") >>
+           month: re_bytes_find!("^[0-9]{2}") >>
            tag!("-") >>
-           day: re_bytes_find!("[0-9]{2}") >>
+           day: re_bytes_find!("^[0-9]{2}") >>
            (
                Local.ymd(String::from_utf8_lossy(year).into_owned().parse::<i32>().unwrap(), 
                          String::from_utf8_lossy(month).into_owned().parse::<u32>().unwrap(), 
@@ -75,28 +77,12 @@ named!(onlydate<chrono::DateTime<Local>>,
        )
 );
 
-named!(onlytime<chrono::DateTime<Local>>,
-       do_parse!(
-        hour: re_bytes_find!("[0-9]{2}") >>
-        tag!(":") >>
-        minute: re_bytes_find!("[0-9]{2}") >>
-        (
-            match Local::now().with_hour(String::from_utf8_lossy(hour).into_owned().parse::<u32>().unwrap()) {
-                Some(t) => t.with_minute(String::from_utf8_lossy(minute).into_owned().parse::<u32>().unwrap()).unwrap(),
-                None    => {
-                    eprintln!("Error setting time for today!");
-                    process::exit(1);
-                },
-            }
-        )
-      )
-   );
-
 pub struct TimeFilter {
     comp_op: filter::CompOp,
     attribute: filter::Attribute,
-    epsilon: u64,
-    timestamp: u64,
+    epsilon: Duration,
+    /// Time since the epoch, which may have nanoseconds.
+    timestamp: Duration,
     operator_flip: bool,
 }
 
@@ -116,35 +102,35 @@ fn parse_abs_date(param: &str) -> Option<chrono::DateTime<Local>> {
             },
     };
 
-    return d;
+    d
 }
 
+/// Parses `HH:MM` on today's date.
 fn parse_abs_time(param: &str) -> Option<chrono::DateTime<Local>> {
-    let t : Option<chrono::DateTime<Local>> = match onlytime(param.as_bytes()) {
-        IResult::Done(_, q)     => Some(q),
-        IResult::Error(_)       => None,
-        IResult::Incomplete(n)  => match n {
-                Needed::Unknown => {
-                    eprintln!("Need more input, but I haven't got a clou how much!");
-                    process::exit(1);
-                },
-                Needed::Size(n) => {
-                    eprintln!("Need {}bytes more input!", n);
-                    process::exit(1);
-                },
-            },
-    };
+    NaiveTime::parse_from_str(param, "%H:%M").ok().and_then(|t| Local::today().and_time(t))
+}
 
-    return t;
+/// Parses `HH:MM:SS` with an optional fraction of a second on today's date.
+fn parse_abs_time_seconds(param: &str) -> Option<chrono::DateTime<Local>> {
+    NaiveTime::parse_from_str(param, "%H:%M:%S%.f").ok().and_then(|t| Local::today().and_time(t))
 }
 
 fn parse_abs_datetime(param: &str) -> Option<chrono::DateTime<Local>> {
-    let dt = Local.datetime_from_str(param, "%Y-%m-%d %H:%M");
-    if dt.is_ok() {
-        Some(dt.unwrap())
-    } else {
-        None
+    Local.datetime_from_str(param, "%Y-%m-%d %H:%M").ok()
+}
+
+/// Parses `YYYY-MM-DD HH:MM:SS` with an optional fraction of a second like `.25`.
+fn parse_abs_datetime_seconds(param: &str) -> Option<chrono::DateTime<Local>> {
+    Local.datetime_from_str(param, "%Y-%m-%d %H:%M:%S%.f").ok()
+}
+
+/// Times before the epoch are taken as the epoch itself, as no entry can be
+/// earlier for the comparisons, see `get_attribute`.
+fn since_epoch(datetime: chrono::DateTime<Local>) -> Duration {
+    if datetime.timestamp() < 0 {
+        return Duration::new(0, 0);
     }
+    Duration::new(datetime.timestamp() as u64, datetime.nanosecond())
 }
 
 
@@ -152,6 +138,10 @@ impl TimeFilter {
     pub fn new(attribute: filter::Attribute, comp_op: filter::CompOp, param: &str) -> TimeFilter{
         // Disclaimer: I will not care about leap-anything until I do. 
         // Oh, and every month has 30 days....
+        // seconds since the epoch, as printed with --machine-readable
+        if let Some(timestamp) = timestamps::parse_epoch(param) {
+            return TimeFilter{attribute, comp_op, timestamp, operator_flip: false, epsilon: Duration::new(0, 0)};
+        }
         let reltime: Option<RelativeTimeTuple> = match reltime_parameter(param.as_bytes()) {
             IResult::Done(_, q) => Some(q),
             IResult::Error(_)   => None,
@@ -167,101 +157,147 @@ impl TimeFilter {
                 },
         };
 
-        let mut offset = 0u64;
+        let offset;
         let mut flip = false;
-        let mut epsilon = 0u64;
+        let mut epsilon = Duration::new(0, 0);
 
-        if reltime.is_some(){
-            let t = reltime.unwrap();
+        if let Some(t) = reltime {
             let seconds = match t.unit {
                 RelativeTimeUnit::Second => t.dist,
-                RelativeTimeUnit::Minute => t.dist * 60,
-                RelativeTimeUnit::Hour   => t.dist * 60 * 60,
-                RelativeTimeUnit::Day    => t.dist * 60 * 60 * 24,
-                RelativeTimeUnit::Week   => t.dist * 60 * 60 * 24 * 7,
-                RelativeTimeUnit::Month  => t.dist * 60 * 60 * 24 * 30,
-                RelativeTimeUnit::Year   => t.dist * 60 * 60 * 24 * 365,
+                RelativeTimeUnit::Minute => t.dist * 60.0,
+                RelativeTimeUnit::Hour   => t.dist * 60.0 * 60.0,
+                RelativeTimeUnit::Day    => t.dist * 60.0 * 60.0 * 24.0,
+                RelativeTimeUnit::Week   => t.dist * 60.0 * 60.0 * 24.0 * 7.0,
+                RelativeTimeUnit::Month  => t.dist * 60.0 * 60.0 * 24.0 * 30.0,
+                RelativeTimeUnit::Year   => t.dist * 60.0 * 60.0 * 24.0 * 365.0,
             };
 
-            // this will break if:
-            //   * after Fri, 11 Apr 2262 23:47:16 GMT
-            //   * checking for things before 1970-01-01
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            offset = now - seconds.abs() as u64;
+            // a distance reaching before 1970-01-01 ends at the epoch, see since_epoch
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let distance = Duration::try_from_secs_f64(seconds.abs()).ok();
+            offset = distance.and_then(|d| now.checked_sub(d)).unwrap_or_default();
 
             flip = match comp_op {
-                filter::CompOp::Lower => seconds < 0, 
-                filter::CompOp::LowerEqual => seconds < 0, 
-                filter::CompOp::Greater => seconds < 0, 
-                filter::CompOp::GreaterEqual => seconds < 0, 
+                filter::CompOp::Lower => seconds < 0.0, 
+                filter::CompOp::LowerEqual => seconds < 0.0, 
+                filter::CompOp::Greater => seconds < 0.0, 
+                filter::CompOp::GreaterEqual => seconds < 0.0, 
                 _    => false,
             };
         } else {
-            let abs_datetime = parse_abs_datetime(param);
-            let abs_date = parse_abs_date(param);
-            let abs_time = parse_abs_time(param);
-            if abs_datetime.is_some(){
-                offset = abs_datetime.unwrap().timestamp() as u64;
-                epsilon = 60 as u64;
-            } else if abs_date.is_some() {
-                offset = abs_date.unwrap().timestamp() as u64;
-                epsilon = 60 * 60 * 24 as u64;
-            } else if abs_time.is_some() {
-                offset = abs_time.unwrap().timestamp() as u64;
-                epsilon = 60 as u64;
+            if let Some(datetime) = parse_abs_datetime_seconds(param) {
+                offset = since_epoch(datetime);
+                // a time with a fraction is exact, otherwise it is the whole second
+                if !param.contains('.') {
+                    epsilon = Duration::new(1, 0);
+                }
+            } else if let Some(datetime) = parse_abs_datetime(param) {
+                offset = since_epoch(datetime);
+                epsilon = Duration::new(60, 0);
+            } else if let Some(date) = parse_abs_date(param) {
+                offset = since_epoch(date);
+                epsilon = Duration::new(60 * 60 * 24, 0);
+            } else if let Some(time) = parse_abs_time_seconds(param) {
+                offset = since_epoch(time);
+                if !param.contains('.') {
+                    epsilon = Duration::new(1, 0);
+                }
+            } else if let Some(time) = parse_abs_time(param) {
+                offset = since_epoch(time);
+                epsilon = Duration::new(60, 0);
             } else {
-                eprintln!("Could not parse absolute datetime format {}. Supported datetime formats are: YYYY-MM-DD HH:MM, YYYY-MM-DD, HH:MM", param);
+                eprintln!("Could not parse absolute datetime format {}. Supported datetime formats are: YYYY-MM-DD HH:MM:SS.NNN, YYYY-MM-DD HH:MM, YYYY-MM-DD, HH:MM:SS.NNN, HH:MM and seconds since the epoch", param);
                 process::exit(1);
             }
         }
-        TimeFilter{attribute: attribute, comp_op: comp_op, timestamp: offset, operator_flip: flip, epsilon: epsilon }
+        TimeFilter{attribute, comp_op, timestamp: offset, operator_flip: flip, epsilon }
     }
 
-    fn get_attribute(&self, dir_entry: &DirEntry) -> Duration {
-        // to many unwraps on to little lines... this will go wrong really fast
-        match self.attribute {
-            filter::Attribute::Mtime => dir_entry.metadata().unwrap().modified().unwrap().duration_since(UNIX_EPOCH).unwrap(),
-            filter::Attribute::Atime => match dir_entry.metadata().unwrap().accessed(){
-                Ok(t) => t.duration_since(UNIX_EPOCH).unwrap(),
-                Err(e) => {
-                    eprintln!("Atime error: {}", e);
-                    process::exit(1);
-                },
-            },
-            filter::Attribute::Ctime => match dir_entry.metadata().unwrap().created(){
-                Ok(t) => t.duration_since(UNIX_EPOCH).unwrap(),
-                Err(e) => {
-                    eprintln!("Ctime error: {}", e);
-                    process::exit(1);
-                },
-            },
-            _ => {
-                eprintln!("{:?} is not a type of time.", self.attribute);
-                process::exit(1);
-            },
-        }
+    /// The time of the entry since the epoch, None if it is not available,
+    /// e.g. the birth time on filesystems which do not record it.
+    fn get_attribute(&self, dir_entry: &DirEntry) -> Option<Duration> {
+        let metadata = dir_entry.metadata().ok()?;
+        let time = timestamps::of(&self.attribute, &metadata).ok()?;
+        // times before the epoch are not supported by the comparisons
+        time.duration_since(UNIX_EPOCH).ok()
     }
 }
 
 impl Filter for TimeFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
+        let time = match self.get_attribute(dir_entry) {
+            Some(t) => t,
+            None    => return false,
+        };
         let res = match self.comp_op {
             filter::CompOp::Equal        => {
-                if self.epsilon == 0 {
-                    self.get_attribute(dir_entry) == Duration::new(self.timestamp, 0)
+                if self.epsilon == Duration::new(0, 0) {
+                    time == self.timestamp
                 } else {
-                    Duration::new(self.timestamp, 0) <= self.get_attribute(dir_entry) && self.get_attribute(dir_entry) <= Duration::new(self.timestamp + self.epsilon, 0)
+                    self.timestamp <= time && time <= self.timestamp + self.epsilon
                 }
             },
-            filter::CompOp::Lower        => self.get_attribute(dir_entry) <  Duration::new(self.timestamp, 0),
-            filter::CompOp::LowerEqual   => self.get_attribute(dir_entry) <= Duration::new(self.timestamp, 0),
-            filter::CompOp::Greater      => self.get_attribute(dir_entry) >  Duration::new(self.timestamp, 0),
-            filter::CompOp::GreaterEqual => self.get_attribute(dir_entry) >= Duration::new(self.timestamp, 0),
+            filter::CompOp::Lower        => time <  self.timestamp,
+            filter::CompOp::LowerEqual   => time <= self.timestamp,
+            filter::CompOp::Greater      => time >  self.timestamp,
+            filter::CompOp::GreaterEqual => time >= self.timestamp,
             _ => {
                 eprintln!("Comparison operator {:?} is not supported for relative time comparisons.", self.comp_op);
                 process::exit(1);
             },
         };
-        return if self.operator_flip { !res } else { res }
+        if self.operator_flip { !res } else { res }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeFilter;
+    use command_attribute::CommandAttributes;
+    use filter::{Attribute, CompOp, Filter, Search};
+    use chrono::prelude::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use walkdir::WalkDir;
+
+    fn today_at(hour: u32, minute: u32, second: u32, nanosecond: u32) -> SystemTime {
+        let time = Local::today().and_hms_nano(hour, minute, second, nanosecond);
+        UNIX_EPOCH + Duration::new(time.timestamp() as u64, time.nanosecond())
+    }
+
+    fn passes(path: &Path, comp_op: CompOp, param: &str) -> bool {
+        let entry = WalkDir::new(path).into_iter().next().unwrap().unwrap();
+        let commands = CommandAttributes::new();
+        let search = Search{root: path, commands: &commands};
+        TimeFilter::new(Attribute::Mtime, comp_op, param).test(&entry, &search)
+    }
+
+    #[test]
+    fn parses_times_of_today() {
+        let at = |param: &str| {
+            let filter = TimeFilter::new(Attribute::Mtime, CompOp::Greater, param);
+            (UNIX_EPOCH + filter.timestamp, filter.epsilon)
+        };
+        assert_eq!(at("10:00"), (today_at(10, 0, 0, 0), Duration::new(60, 0)));
+        assert_eq!(at("9:59:00"), (today_at(9, 59, 0, 0), Duration::new(1, 0)));
+        assert_eq!(at("10:00:30.7"), (today_at(10, 0, 30, 700000000), Duration::new(0, 0)));
+    }
+
+    #[test]
+    fn compares_times_of_today_with_seconds() {
+        let path = env::temp_dir().join(format!("frust-time-filter-{}", process::id()));
+        let file = fs::File::create(&path).unwrap();
+        file.set_modified(today_at(10, 0, 30, 500000000)).unwrap();
+        assert!(passes(&path, CompOp::Greater, "10:00:30.2"));
+        assert!(!passes(&path, CompOp::Greater, "10:00:30.7"));
+        assert!(!passes(&path, CompOp::Greater, "10:00:40"));
+        assert!(passes(&path, CompOp::Greater, "9:59:00"));
+        assert!(passes(&path, CompOp::Equal, "10:00:30"));
+        assert!(passes(&path, CompOp::Equal, "10:00"));
+        assert!(!passes(&path, CompOp::Equal, "10:00:31"));
+        fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,97 @@
+//! Time attributes of files
+//!
+//! `ctime` is the POSIX status change time, which is updated whenever the inode
+//! changes, e.g. by `chmod` or a rename. The birth time `btime` is the creation
+//! time of the file, which the standard library reads with `statx` where the
+//! kernel and the filesystem support it. If they don't, it is missing.
+
+use filter::Attribute;
+use std::fs::Metadata;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Converts seconds and nanoseconds since the epoch, as in a `stat` buffer.
+fn from_epoch(seconds: i64, nanoseconds: i64) -> SystemTime {
+    let nanoseconds = nanoseconds as u32;
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::new(seconds as u64, nanoseconds)
+    } else {
+        UNIX_EPOCH - Duration::new(seconds.unsigned_abs(), 0) + Duration::new(0, nanoseconds)
+    }
+}
+
+/// The status change time of a file.
+pub fn ctime(metadata: &Metadata) -> SystemTime {
+    from_epoch(metadata.ctime(), metadata.ctime_nsec())
+}
+
+/// Returns one of the time attributes of a file, an error if the platform or
+/// filesystem does not record it.
+pub fn of(attribute: &Attribute, metadata: &Metadata) -> io::Result<SystemTime> {
+    match *attribute {
+        Attribute::Mtime => metadata.modified(),
+        Attribute::Atime => metadata.accessed(),
+        Attribute::Ctime => Ok(ctime(metadata)),
+        Attribute::Btime => metadata.created(),
+        _                => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not a type of time", attribute))),
+    }
+}
+
+/// Seconds since the epoch with nanoseconds, e.g. `1500000000.123456789`.
+pub fn epoch_string(time: SystemTime) -> String {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d)  => format!("{}.{:09}", d.as_secs(), d.subsec_nanos()),
+        Err(e) => format!("-{}.{:09}", e.duration().as_secs(), e.duration().subsec_nanos()),
+    }
+}
+
+/// Parses seconds since the epoch with up to nine decimals, as printed by `epoch_string`.
+pub fn parse_epoch(text: &str) -> Option<Duration> {
+    let mut parts = text.splitn(2, '.');
+    let seconds = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("0");
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_number(seconds) || !is_number(fraction) || fraction.len() > 9 {
+        return None;
+    }
+    let nanoseconds = format!("{:0<9}", fraction).parse::<u32>().ok()?;
+    Some(Duration::new(seconds.parse::<u64>().ok()?, nanoseconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{epoch_string, from_epoch, parse_epoch};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn prints_nanoseconds() {
+        assert_eq!(epoch_string(UNIX_EPOCH + Duration::new(1500000000, 123456789)), "1500000000.123456789");
+        assert_eq!(epoch_string(UNIX_EPOCH + Duration::new(5, 1)), "5.000000001");
+        assert_eq!(epoch_string(UNIX_EPOCH), "0.000000000");
+        assert_eq!(epoch_string(UNIX_EPOCH - Duration::new(1, 500000000)), "-1.500000000");
+    }
+
+    #[test]
+    fn converts_stat_times() {
+        assert_eq!(from_epoch(10, 5), UNIX_EPOCH + Duration::new(10, 5));
+        // stat counts the nanoseconds forward from the seconds, also before the epoch
+        assert_eq!(from_epoch(-2, 500000000), UNIX_EPOCH - Duration::new(1, 500000000));
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        let time = Duration::new(1500000000, 123456789);
+        assert_eq!(parse_epoch(&epoch_string(UNIX_EPOCH + time)), Some(time));
+        assert_eq!(parse_epoch("1500000000"), Some(Duration::new(1500000000, 0)));
+        assert_eq!(parse_epoch("1.5"), Some(Duration::new(1, 500000000)));
+        assert_eq!(parse_epoch("1.000000001"), Some(Duration::new(1, 1)));
+    }
+
+    #[test]
+    fn rejects_other_numbers() {
+        for text in &["", ".5", "1.", "1.0000000001", "-1", "1e9", "1.5h", "12:30", "2017-01-01"] {
+            assert_eq!(parse_epoch(text), None, "{}", text);
+        }
+    }
+}
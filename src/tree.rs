@@ -0,0 +1,104 @@
+//! Tree view of the matching entries
+//!
+//! With `--tree` the matches of a search root are collected and printed as an
+//! indented hierarchy after the root has been walked. Directories which lead to a
+//! match are shown even if they do not match themselves, and every directory line
+//! ends with the number and the total size of the matches below it.
+
+use formatter;
+use ColorConfig;
+use walkdir::DirEntry;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Default)]
+struct Node {
+    matched: bool,
+    file_type: Option<fs::FileType>,
+    size: u64,
+    children: BTreeMap<OsString, Node>,
+}
+
+impl Node {
+    /// Number and total size of the matches below this node.
+    fn totals(&self) -> (usize, u64) {
+        self.children.values().fold((0, 0), |(count, size), child| {
+            let (c, s) = child.totals();
+            let own = if child.matched { (1, if child.is_dir() { 0 } else { child.size }) } else { (0, 0) };
+            (count + c + own.0, size + s + own.1)
+        })
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type.map(|t| t.is_dir()).unwrap_or(!self.children.is_empty())
+    }
+}
+
+pub struct Tree {
+    root: PathBuf,
+    top: Node,
+}
+
+impl Tree {
+    pub fn new(root: &Path) -> Tree {
+        Tree{root: root.to_path_buf(), top: Node::default()}
+    }
+
+    /// Adds a matching entry together with the directories leading to it.
+    pub fn insert(&mut self, dir_entry: &DirEntry) {
+        let relative = dir_entry.path().strip_prefix(&self.root).unwrap_or(dir_entry.path());
+        let mut node = &mut self.top;
+        for component in relative.components() {
+            if let Component::Normal(name) = component {
+                node = node.children.entry(name.to_os_string()).or_default();
+            }
+        }
+        node.matched = true;
+        node.file_type = Some(dir_entry.file_type());
+        node.size = dir_entry.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+
+    /// Prints the tree, nothing is printed if no entry matched.
+    pub fn print(&self, color_config: &Option<ColorConfig>, color_mode: bool, hyperlinks: bool, machine_mode: bool) {
+        if !self.top.matched && self.top.children.is_empty() {
+            return;
+        }
+        let root_type = fs::symlink_metadata(&self.root).map(|m| m.file_type()).ok();
+        let name = match root_type {
+            Some(t) => formatter::format_typed_name(&self.root.to_string_lossy(), &self.root, t, color_config, color_mode, hyperlinks),
+            None    => self.root.display().to_string(),
+        };
+        println!("{}{}", name, summary(&self.top, machine_mode));
+        print_children(&self.top, &self.root, "", color_config, color_mode, hyperlinks, machine_mode);
+    }
+}
+
+fn summary(node: &Node, machine_mode: bool) -> String {
+    if !node.is_dir() {
+        return String::new();
+    }
+    let (count, size) = node.totals();
+    if count == 0 {
+        return String::new();
+    }
+    format!(" ({} {}, {})", count, if count == 1 { "match" } else { "matches" }, formatter::format_filesize(size, machine_mode))
+}
+
+fn print_children(node: &Node, path: &Path, indent: &str, color_config: &Option<ColorConfig>, color_mode: bool, hyperlinks: bool, machine_mode: bool) {
+    let last = node.children.len().saturating_sub(1);
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let child_path = path.join(name);
+        // a newline in a name would break the tree
+        let name: String = name.to_string_lossy().chars().map(|c| if c.is_control() { '?' } else { c }).collect();
+        let file_type = child.file_type.or_else(|| fs::symlink_metadata(&child_path).map(|m| m.file_type()).ok());
+        let formatted = match file_type {
+            Some(t) => formatter::format_typed_name(&name, &child_path, t, color_config, color_mode, hyperlinks),
+            None    => name,
+        };
+        let (branch, continuation) = if i == last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        println!("{}{}{}{}", indent, branch, formatted, summary(child, machine_mode));
+        print_children(child, &child_path, &format!("{}{}", indent, continuation), color_config, color_mode, hyperlinks, machine_mode);
+    }
+}
@@ -1,5 +1,6 @@
 use filter;
 use filter::Filter;
+use filter::Search;
 use walkdir::DirEntry;
 use std::os::unix::fs::MetadataExt;
 use std::process;
@@ -11,12 +12,12 @@ pub struct UidFilter {
 
 impl UidFilter {
     pub fn new(comp_op: filter::CompOp, uid: u32) -> UidFilter {
-        UidFilter{comp_op: comp_op, uid: uid}
+        UidFilter{comp_op, uid}
     }
 }
 
 impl Filter for UidFilter {
-    fn test(&self, dir_entry: &DirEntry) -> bool {
+    fn test(&self, dir_entry: &DirEntry, _search: &Search) -> bool {
         match self.comp_op {
             filter::CompOp::Equal => self.uid == dir_entry.metadata().unwrap().uid(),
             filter::CompOp::Unequal => self.uid != dir_entry.metadata().unwrap().uid(),